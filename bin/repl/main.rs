@@ -1,6 +1,7 @@
-use t8n::*;
-
 fn main() {
     let mut repl = t8n::repl::Repl::new();
-    repl.run();
+    if let Err(e) = repl.run() {
+        println!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -1,4 +1,12 @@
-use t8n::context::{Alloc, Context, TransactionT8n};
+use t8n::asm;
+use t8n::batch::{self, Summary};
+use t8n::blockchain_test::BlockchainTest;
+use t8n::config::Config;
+use t8n::context::{Alloc, Context, Indexes, RunOutput, TransactionT8n, Verification};
+use t8n::diff::DiffReport;
+use t8n::error::Error;
+use t8n::fork::HardFork;
+use t8n::types::{Address, Bytes, U256};
 
 use clap::Parser;
 
@@ -7,88 +15,366 @@ struct Args {
     #[arg(short, long)]
     t8ntool: String,
     #[arg(short, long)]
-    data: Option<String>,
+    data: Option<Bytes>,
+    // Hex bytecode or assembly
+    #[arg(short, long, value_parser = asm::parse_code)]
+    code: Option<Bytes>,
     #[arg(short, long)]
-    code: Option<String>,
-    #[arg(short, long)]
-    gas: Option<String>,
+    gas: Option<U256>,
     #[arg(short, long)]
     evm: Option<String>,
-    #[arg(short = 'f', long)]
-    hard_fork: String,
+    // Without a fork, state tests run the cases of every fork in their post section
+    #[arg(short = 'f', long, required_unless_present_any = ["blockchain_test", "state_test"])]
+    hard_fork: Option<HardFork>,
+    // State test files, directories searched recursively or glob patterns, can be repeated
     #[arg(short = 's', long)]
-    state_test: Option<String>,
+    state_test: Vec<String>,
+    #[arg(long)]
+    test: Option<String>,
+    #[arg(long, value_name = "DATA,GAS,VALUE")]
+    indexes: Option<String>,
+    #[arg(short, long)]
+    receiver: Option<Address>,
     #[arg(short, long)]
-    receiver: Option<String>
+    blockchain_test: Option<String>,
+    // Sign transactions locally instead of passing their secret key to t8n
+    #[arg(long)]
+    sign: bool,
+    // Second t8n tool every case is also run with, the outputs are compared
+    #[arg(long, value_name = "T8NTOOL", conflicts_with = "blockchain_test")]
+    compare_with: Option<String>,
+    // EVM used with --compare-with, when only this is given the same t8n tool is used
+    #[arg(long, value_name = "EVM", conflicts_with = "blockchain_test")]
+    compare_evm: Option<String>,
+    // Number of t8n invocations running at the same time
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+    // Writes the control flow graph of --code to this file in Graphviz DOT format, with the
+    // edges executed by the run highlighted
+    #[arg(long, value_name = "FILE", requires = "code", conflicts_with_all = ["blockchain_test", "state_test"])]
+    cfg: Option<String>
 }
 
-fn main() {
-    let args = Args::parse();
-
-    let context_result: Result<Context, &'static str>;
+// Account holding --code
+fn receiver(args: &Args) -> Address {
+    args.receiver.unwrap_or(Address::from_low_u64(0x100))
+}
 
-    if args.state_test.is_some() {
-        context_result = Context::from_state_test(args.state_test.unwrap().as_str());
-        if context_result.is_err() {
-            println!("Error getting information from state test file");
-            return;
+// Applies the command line options to a context, options that cannot be applied are
+// invalid input
+fn configure(ctx: &mut Context, args: &Args) -> Result<(), Error> {
+    // Set Hard Fork
+    if let Some(hard_fork) = args.hard_fork {
+        ctx.config.hard_fork = hard_fork;
+        // State tests bring their own environment
+        if args.state_test.is_empty() {
+            ctx.env.apply_fork_defaults(hard_fork);
         }
-    } else {
-        context_result = Ok(Context::default());
     }
 
-    let mut ctx = context_result.unwrap();
-
-    // Set Hard Fork
-    ctx.config.hard_fork = args.hard_fork.clone();
-
     // Set t8n
     ctx.config.t8n = args.t8ntool.clone();
 
     // Set EVM if provided
-    if args.evm.is_some() {
-        ctx.config.evm = args.evm.unwrap().to_string();
+    if let Some(evm) = &args.evm {
+        ctx.config.evm = evm.to_string();
     }
 
     // Set code if provided
     if args.code.is_some() || args.data.is_some() {
-        let _ = ctx.add_default_address(); 
         // Create sender account
-        let snd_address = String::from("0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+        let snd_address = ctx.add_default_address()?;
 
         // Create default transaction
         ctx.txs.push(TransactionT8n::default());
 
         // Set sender
-        let pk = ctx.get_secret_key(snd_address);
-        ctx.txs[0].set_private_key(pk.unwrap().as_str());
+        let pk = ctx.get_secret_key(&snd_address)
+            .ok_or_else(|| Error::invalid(format!("No secret key for sender {}", snd_address)))?;
+        ctx.txs[0].set_private_key(pk);
 
-        if args.code.is_some() {
+        if let Some(code) = &args.code {
             // Create receiver account
-            let rec_address: String;
-            if args.receiver.is_some() {
-                rec_address = args.receiver.unwrap();
-            } else {
-                rec_address = String::from("0x0000000000000000000000000000000000000100");
-            }
-            let _ = ctx.add_address(&rec_address);
-            let mut account: Alloc = ctx.alloc.get(&rec_address).unwrap().clone();
-            let _ = account.set_code(args.code.unwrap()); 
+            let rec_address = receiver(args);
+            let _ = ctx.add_address(rec_address);
+            let mut account: Alloc = ctx.alloc.get(&rec_address)
+                .ok_or_else(|| Error::invalid(format!("Address {} not found", rec_address)))?.clone();
+            let _ = account.set_code(code.clone());
             let _ = ctx.alloc.remove(&rec_address);
-            ctx.alloc.insert(rec_address.clone(), account.to_owned());
+            ctx.alloc.insert(rec_address, account.to_owned());
             // Set receiver
-            ctx.txs[0].set_receiver(&rec_address);
+            ctx.txs[0].set_receiver(rec_address);
         }
 
-        if args.data.is_some() {
+        if let Some(data) = &args.data {
             // Set input data
-            ctx.txs[0].set_input(args.data.unwrap().as_str());
+            ctx.txs[0].set_input(data.clone());
         }
     }
 
-    if args.gas.is_some() {
-        ctx.txs[0].set_gas(&args.gas.unwrap());
+    if let Some(gas) = args.gas {
+        let tx = ctx.txs.first_mut().ok_or_else(|| Error::invalid("--gas requires a transaction, use --code, --data or --state-test"))?;
+        tx.set_gas(gas);
+    }
+    Ok(())
+}
+
+// Exit status: 0 when every case passed, 1 when some case did not match its expectations
+// and one of the codes below when a case could not be executed at all
+const EXIT_FAILED: i32 = 1;
+
+fn exit_code(e: &Error) -> i32 {
+    match e {
+        Error::InvalidInput(_) => 2,
+        Error::Io { .. } => 3,
+        Error::Json { .. } => 4,
+        Error::T8nNotFound { .. } => 5,
+        Error::T8nExit { .. } => 6,
+        Error::MalformedFixture(_) => 7
+    }
+}
+
+struct Outcome {
+    output: RunOutput,
+    verification: Option<Verification>,
+    diff: Option<DiffReport>
+}
+
+// Backend of the differential mode, if enabled
+fn compare_config(args: &Args, ctx: &Context) -> Option<Config> {
+    if args.compare_with.is_none() && args.compare_evm.is_none() {
+        return None;
+    }
+    let mut other = ctx.config.clone();
+    if let Some(t8n) = &args.compare_with {
+        other.t8n = t8n.clone();
+    }
+    other.evm = args.compare_evm.clone().unwrap_or_default();
+    Some(other)
+}
+
+// Runs a single context and checks it against its expectations and, with `compare`,
+// against a second backend. Prints nothing so it can be called from the worker threads
+fn execute(ctx: &Context, sign: bool, compare: Option<&Config>) -> Result<Outcome, Error> {
+    let mut ctx = ctx.clone();
+    if sign {
+        let fork = ctx.env.active_fork(ctx.config.hard_fork);
+        for tx in ctx.txs.iter_mut().filter(|tx| tx.has_secret_key()) {
+            tx.sign(fork)?;
+        }
+    }
+    let (output, diff) = match compare {
+        Some(other) => {
+            let (output, _, diff) = ctx.diff_run(other)?;
+            (output, Some(diff))
+        },
+        None => (ctx.run()?, None)
+    };
+    let verification = match ctx.expect {
+        Some(_) => Some(ctx.verify(&output.result)?),
+        None => None
+    };
+    Ok(Outcome { output, verification, diff })
+}
+
+// Prints an outcome, returns true if it matched its expectations. Traces and results are
+// only printed when `verbose`
+fn report(outcome: &Outcome, verbose: bool) -> bool {
+    if verbose {
+        for trace in &outcome.output.traces {
+            trace.print();
+        }
+        outcome.output.result.print();
+        outcome.output.print_delegations();
+    }
+    let passed = match &outcome.verification {
+        Some(verification) => {
+            verification.print();
+            verification.passed()
+        },
+        None => outcome.output.result.rejected.is_empty()
+    };
+    match &outcome.diff {
+        Some(diff) if verbose || !diff.is_empty() => {
+            diff.print();
+            passed && diff.is_empty()
+        },
+        _ => passed
+    }
+}
+
+// Runs every test of a blockchain test file, returns the exit status
+fn run_blockchain_tests(bt_name: &str, args: &Args) -> i32 {
+    let tests = match BlockchainTest::load(bt_name) {
+        Ok(tests) => tests,
+        Err(e) => {
+            println!("Error getting information from blockchain test file: {}", e);
+            return exit_code(&e);
+        }
+    };
+
+    let mut ctx = Context::default();
+    if let Err(e) = configure(&mut ctx, args) {
+        println!("Error: {}", e);
+        return exit_code(&e);
+    }
+
+    let tests: Vec<&BlockchainTest> = tests.iter()
+        .filter(|t| args.test.is_none() || args.test.as_deref() == Some(t.name.as_str()))
+        .collect();
+    let mut status = 0;
+    batch::run_parallel(&tests, args.jobs as usize, |test| test.run(&ctx.config), |ix, result| {
+        match result {
+            Ok(verification) => {
+                verification.print();
+                if !verification.passed() {
+                    status = status.max(EXIT_FAILED);
+                }
+            },
+            Err(e) => {
+                println!("Error running {}: {}", tests[ix].name, e);
+                status = status.max(exit_code(&e));
+            }
+        }
+    });
+    status
+}
+
+fn case_name(ctx: &Context) -> String {
+    match &ctx.expect {
+        Some(expect) => format!("{} {} d{} g{} v{}", expect.test_name, ctx.config.hard_fork, expect.indexes.data, expect.indexes.gas, expect.indexes.value),
+        None => ctx.config.hard_fork.to_string()
+    }
+}
+
+// Runs every case of the state test files, returns the exit status. The summary is only
+// printed when more than one file is run
+fn run_state_tests(args: &Args) -> i32 {
+    let files = match batch::discover(&args.state_test) {
+        Ok(files) => files,
+        Err(e) => {
+            println!("Error looking for state test files: {}", e);
+            return exit_code(&e);
+        }
+    };
+    let batch = files.len() > 1 || args.state_test.iter().any(|s| !std::path::Path::new(s).is_file());
+
+    let mut summary = Summary::default();
+    let mut status = 0;
+    let mut cases: Vec<(String, Context)> = Vec::new();
+    for file in &files {
+        let file = file.to_string_lossy();
+        let (contexts, skipped) = match Context::state_test_batch_cases(&file, args.test.as_deref(), args.hard_fork) {
+            Ok(cases) => cases,
+            Err(e) => {
+                println!("Error getting information from state test file: {}", e);
+                summary.error(file.as_ref(), &e);
+                status = status.max(exit_code(&e));
+                continue;
+            }
+        };
+        summary.skip(skipped);
+        for ctx in contexts {
+            let mut ctx = match ctx {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    summary.error(file.as_ref(), &e);
+                    status = status.max(exit_code(&e));
+                    continue;
+                }
+            };
+            if let Err(e) = configure(&mut ctx, args) {
+                println!("Error: {}", e);
+                summary.error(file.as_ref(), &e);
+                status = status.max(exit_code(&e));
+                continue;
+            }
+            cases.push((file.to_string(), ctx));
+        }
+    }
+
+    batch::run_parallel(&cases, args.jobs as usize, |(_, ctx)| execute(ctx, args.sign, compare_config(args, ctx).as_ref()), |ix, result| {
+        let (file, ctx) = &cases[ix];
+        match result {
+            Ok(outcome) if report(&outcome, !batch) => summary.pass(),
+            Ok(_) => {
+                summary.fail(format!("{} {}", file, case_name(ctx)));
+                status = status.max(EXIT_FAILED);
+            },
+            Err(e) => {
+                println!("Error running {}: {}", case_name(ctx), e);
+                summary.error(format!("{} {}", file, case_name(ctx)), &e);
+                status = status.max(exit_code(&e));
+            }
+        }
+    });
+    if batch {
+        summary.print();
+    }
+    status
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Some(blockchain_test) = &args.blockchain_test {
+        std::process::exit(run_blockchain_tests(blockchain_test, &args));
+    }
+
+    if !args.state_test.is_empty() && args.indexes.is_none() {
+        std::process::exit(run_state_tests(&args));
+    }
+
+    let contexts: Vec<Context> = match (args.state_test.as_slice(), &args.indexes) {
+        ([state_test], Some(indexes)) => {
+            let contexts = match Indexes::parse(indexes) {
+                Some(ix) => Context::from_state_test(state_test, args.test.as_deref(), &ix),
+                None => Err(Error::invalid(format!("Invalid indexes `{}`, expected <data,gas,value>", indexes)))
+            };
+            match contexts {
+                Ok(ctx) => vec![ctx],
+                Err(e) => {
+                    println!("Error getting information from state test file: {}", e);
+                    std::process::exit(exit_code(&e));
+                }
+            }
+        },
+        ([], _) => vec![Context::default()],
+        _ => {
+            let e = Error::invalid("--indexes requires a single state test file");
+            println!("Error: {}", e);
+            std::process::exit(exit_code(&e));
+        }
+    };
+
+    let mut status = 0;
+    for mut ctx in contexts {
+        if let Err(e) = configure(&mut ctx, &args) {
+            println!("Error: {}", e);
+            status = status.max(exit_code(&e));
+            continue;
+        }
+        match execute(&ctx, args.sign, compare_config(&args, &ctx).as_ref()) {
+            Ok(outcome) => {
+                if !report(&outcome, true) {
+                    status = status.max(EXIT_FAILED);
+                }
+                if let Some(file) = &args.cfg {
+                    let written = ctx.cfg_dot(receiver(&args), ctx.config.hard_fork, Some(&outcome.output.traces))
+                        .and_then(|dot| std::fs::write(file, dot).map_err(|e| Error::io(file, e)));
+                    if let Err(e) = written {
+                        println!("Error writing control flow graph: {}", e);
+                        status = status.max(exit_code(&e));
+                    }
+                }
+            },
+            Err(e) => {
+                println!("Error: {}", e);
+                status = status.max(exit_code(&e));
+            }
+        }
     }
 
-    ctx.run();
+    std::process::exit(status);
 }
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::opcode::Opcode;
+use crate::types::{Bytes, U256};
+
+enum Operand {
+    Value(Vec<u8>),
+    Label(String)
+}
+
+// Program item, PUSH sizes of label references are only known once every label is placed
+enum Item {
+    Op(u8),
+    Push { size: Option<usize>, operand: Operand, line: usize },
+    Label(String)
+}
+
+fn is_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Big-endian bytes of a PUSH operand. Hex values keep the width they are written with,
+// decimal values take as few bytes as possible
+fn parse_value(token: &str, line: usize) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::invalid(format!("line {}: invalid value `{}`", line, token));
+    let value: U256 = token.parse().map_err(|_| invalid())?;
+    let bytes = value.to_be_bytes();
+    let minimal = bytes.iter().position(|b| *b != 0).map(|first| 32 - first).unwrap_or(1);
+    let width = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => hex.len().div_ceil(2).max(minimal),
+        None => minimal
+    };
+    if width > 32 {
+        return Err(invalid());
+    }
+    Ok(bytes[32 - width..].to_vec())
+}
+
+fn parse(source: &str) -> Result<Vec<Item>, Error> {
+    let mut items: Vec<Item> = Vec::new();
+    for (line_ix, line) in source.lines().enumerate() {
+        let line_no = line_ix + 1;
+        let code = line.split(';').next().unwrap_or("");
+        let code = code.split("//").next().unwrap_or("");
+        let mut tokens = code.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if let Some(label) = token.strip_suffix(':') {
+                if !is_label_name(label) {
+                    return Err(Error::invalid(format!("line {}: invalid label `{}`", line_no, label)));
+                }
+                items.push(Item::Label(label.to_string()));
+                continue;
+            }
+
+            let upper = token.to_ascii_uppercase();
+            let size = match upper.as_str() {
+                "PUSH" => Some(None),
+                "PUSH0" => None,
+                _ if upper.starts_with("PUSH") => Some(Opcode::from_name(&upper).map(|o| o.immediate)),
+                _ => None
+            };
+            match size {
+                Some(size) => {
+                    if upper != "PUSH" && size.is_none() {
+                        return Err(Error::invalid(format!("line {}: unknown mnemonic `{}`", line_no, token)));
+                    }
+                    let operand = tokens.next().ok_or_else(|| Error::invalid(format!("line {}: {} requires a value", line_no, token)))?;
+                    let operand = match operand.strip_prefix('@') {
+                        Some(label) => Operand::Label(label.to_string()),
+                        None => Operand::Value(parse_value(operand, line_no)?)
+                    };
+                    if let (Some(size), Operand::Value(value)) = (size, &operand) {
+                        if value.len() > size {
+                            return Err(Error::invalid(format!("line {}: value does not fit in {}", line_no, token)));
+                        }
+                    }
+                    items.push(Item::Push { size, operand, line: line_no });
+                },
+                None => {
+                    let opcode = Opcode::from_name(token)
+                        .ok_or_else(|| Error::invalid(format!("line {}: unknown mnemonic `{}`", line_no, token)))?;
+                    items.push(Item::Op(opcode.code));
+                }
+            }
+        }
+    }
+    Ok(items)
+}
+
+// Offsets of every label, given the current PUSH sizes
+fn place_labels(items: &[Item], sizes: &[usize]) -> Result<HashMap<String, usize>, Error> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut offset = 0;
+    for (item, size) in items.iter().zip(sizes) {
+        match item {
+            Item::Label(name) => {
+                if labels.insert(name.clone(), offset).is_some() {
+                    return Err(Error::invalid(format!("label `{}` defined twice", name)));
+                }
+            },
+            Item::Op(_) => offset += 1,
+            Item::Push { .. } => offset += 1 + size
+        }
+    }
+    Ok(labels)
+}
+
+fn operand_bytes(operand: &Operand, labels: &HashMap<String, usize>, line: usize) -> Result<Vec<u8>, Error> {
+    match operand {
+        Operand::Value(value) => Ok(value.clone()),
+        Operand::Label(name) => {
+            let offset = labels.get(name).ok_or_else(|| Error::invalid(format!("line {}: undefined label `{}`", line, name)))?;
+            let bytes = (*offset as u64).to_be_bytes();
+            let first = bytes.iter().position(|b| *b != 0).unwrap_or(7);
+            Ok(bytes[first..].to_vec())
+        }
+    }
+}
+
+// Assembles mnemonics into bytecode. `PUSH <value>` picks the smallest PUSH that fits,
+// `name:` marks a position (usually a JUMPDEST) that `PUSH @name` pushes. `;` and `//`
+// start comments
+pub fn assemble(source: &str) -> Result<Bytes, Error> {
+    let items = parse(source)?;
+
+    // Label pushes start with one byte and grow until every offset fits
+    let mut sizes: Vec<usize> = items.iter().map(|item| match item {
+        Item::Push { size: Some(size), .. } => *size,
+        Item::Push { size: None, operand: Operand::Value(value), .. } => value.len(),
+        Item::Push { size: None, operand: Operand::Label(_), .. } => 1,
+        _ => 0
+    }).collect();
+    let labels = loop {
+        let labels = place_labels(&items, &sizes)?;
+        let mut grown = false;
+        for (item, size) in items.iter().zip(sizes.iter_mut()) {
+            if let Item::Push { size: None, operand: operand @ Operand::Label(_), line } = item {
+                let needed = operand_bytes(operand, &labels, *line)?.len();
+                if needed > *size {
+                    *size = needed;
+                    grown = true;
+                }
+            }
+        }
+        if !grown {
+            break labels;
+        }
+    };
+
+    let mut code: Vec<u8> = Vec::new();
+    for (item, size) in items.iter().zip(&sizes) {
+        match item {
+            Item::Label(_) => {},
+            Item::Op(op) => code.push(*op),
+            Item::Push { operand, line, .. } => {
+                let value = operand_bytes(operand, &labels, *line)?;
+                if value.len() > *size {
+                    return Err(Error::invalid(format!("line {}: label offset does not fit in PUSH{}", line, size)));
+                }
+                let opcode = Opcode::push(*size).ok_or_else(|| Error::invalid(format!("line {}: invalid PUSH size {}", line, size)))?;
+                code.push(opcode.code);
+                code.resize(code.len() + size - value.len(), 0);
+                code.extend(value);
+            }
+        }
+    }
+    Ok(Bytes::from(code))
+}
+
+// Code given by the user: a single hex token is taken as bytecode, anything else is assembled
+pub fn parse_code(code: &str) -> Result<Bytes, Error> {
+    let code = code.trim();
+    if !code.contains(char::is_whitespace) {
+        if let Ok(bytes) = code.parse::<Bytes>() {
+            return Ok(bytes);
+        }
+    }
+    assemble(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(source: &str) -> Vec<u8> {
+        parse_code(source).unwrap().0
+    }
+
+    #[test]
+    fn hex_is_taken_as_bytecode() {
+        assert_eq!(code("0x6001600201"), vec![0x60, 0x01, 0x60, 0x02, 0x01]);
+        assert_eq!(code("  0x00\n"), vec![0x00]);
+        assert_eq!(code("STOP"), vec![0x00]);
+    }
+
+    #[test]
+    fn assembles_mnemonics_and_comments() {
+        assert_eq!(code("PUSH1 1 PUSH1 0x02 add"), vec![0x60, 0x01, 0x60, 0x02, 0x01]);
+        assert_eq!(code("PUSH1 1 ; first\n// nothing here\nPUSH0 STOP // done"), vec![0x60, 0x01, 0x5f, 0x00]);
+    }
+
+    #[test]
+    fn push_sizes() {
+        // Smallest PUSH that fits decimal values, hex values keep their width
+        assert_eq!(code("PUSH 0"), vec![0x60, 0x00]);
+        assert_eq!(code("PUSH 255"), vec![0x60, 0xff]);
+        assert_eq!(code("PUSH 256"), vec![0x61, 0x01, 0x00]);
+        assert_eq!(code("PUSH 0x0001"), vec![0x61, 0x00, 0x01]);
+        assert_eq!(code("PUSH 0x100"), vec![0x61, 0x01, 0x00]);
+        // Explicit sizes are padded
+        assert_eq!(code("PUSH3 1"), vec![0x62, 0x00, 0x00, 0x01]);
+        let mut push32 = vec![0x7f];
+        push32.extend([0xff; 32]);
+        assert_eq!(code(&format!("PUSH 0x{}", "ff".repeat(32))), push32);
+
+        assert!(parse_code("PUSH1 256").is_err());
+        assert!(parse_code(&format!("PUSH 0x{}", "ff".repeat(33))).is_err());
+        assert!(parse_code("PUSH33 1").is_err());
+        assert!(parse_code("PUSH1").is_err());
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(code("PUSH @end JUMP end: JUMPDEST STOP"), vec![0x60, 0x03, 0x56, 0x5b, 0x00]);
+        assert_eq!(code("start: JUMPDEST PUSH @start JUMP"), vec![0x5b, 0x60, 0x00, 0x56]);
+        assert_eq!(code("PUSH2 @end JUMP end: JUMPDEST"), vec![0x61, 0x00, 0x04, 0x56, 0x5b]);
+    }
+
+    #[test]
+    fn label_pushes_grow_until_offsets_fit() {
+        // With a PUSH1 `end` would be at 303, the PUSH2 moves it to 304
+        let source = format!("PUSH @end JUMP {} end: JUMPDEST", "STOP ".repeat(300));
+        let assembled = code(&source);
+        assert_eq!(assembled.len(), 305);
+        assert_eq!(assembled[..4], [0x61, 0x01, 0x30, 0x56]);
+        assert_eq!(assembled[304], 0x5b);
+    }
+
+    #[test]
+    fn invalid_programs() {
+        assert!(parse_code("PUSH @missing JUMP").is_err());
+        assert!(parse_code("a: JUMPDEST a: JUMPDEST").is_err());
+        assert!(parse_code("1a: JUMPDEST").is_err());
+        assert!(parse_code("PUSH1 1 NOTANOPCODE").is_err());
+        assert!(parse_code("PUSH1 1 PUSH1 zz").is_err());
+    }
+}
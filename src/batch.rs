@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::error::Error;
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'))
+}
+
+// Matches a single path component against a pattern with `*`, `?` and `[...]` classes
+fn matches_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches_component(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches_component(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let close = match pattern.iter().position(|c| *c == ']') {
+                Some(close) => close,
+                None => return name.first() == Some(&'[') && matches_component(&pattern[1..], &name[1..])
+            };
+            let (class, negated) = match pattern[1..close].split_first() {
+                Some(('!', rest)) | Some(('^', rest)) => (rest, true),
+                _ => (&pattern[1..close], false)
+            };
+            let c = match name.first() {
+                Some(c) => *c,
+                None => return false
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    found |= class[i] == c;
+                    i += 1;
+                }
+            }
+            found != negated && matches_component(&pattern[close + 1..], &name[1..])
+        },
+        Some(p) => name.first() == Some(p) && matches_component(&pattern[1..], &name[1..])
+    }
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(&dir.to_string_lossy(), e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        paths.push(entry.map_err(|e| Error::io(&dir.to_string_lossy(), e))?.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Directory that is not a symlink, recursive searches do not follow symlinked
+// directories so a link loop cannot make them run forever
+fn is_real_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+// Every JSON file below `dir`, hidden entries and symlinked directories are ignored
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for path in read_dir_sorted(dir)? {
+        if is_hidden(&path) {
+            continue;
+        }
+        if is_real_dir(&path) {
+            walk(&path, files)?;
+        } else if !path.is_dir() && path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Expands the remaining pattern components below `base`, `**` matches any number of
+// directories without following hidden or symlinked ones
+fn expand(base: &Path, components: &[&str], out: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => {
+            out.push(base.to_path_buf());
+            return Ok(());
+        }
+    };
+    if !is_pattern(component) {
+        let path = base.join(component);
+        if path.exists() {
+            expand(&path, rest, out)?;
+        }
+        return Ok(());
+    }
+    if !base.is_dir() {
+        return Ok(());
+    }
+    if *component == "**" {
+        expand(base, rest, out)?;
+        for path in read_dir_sorted(base)?.into_iter().filter(|p| !is_hidden(p) && is_real_dir(p)) {
+            expand(&path, components, out)?;
+        }
+        return Ok(());
+    }
+    let pattern: Vec<char> = component.chars().collect();
+    for path in read_dir_sorted(base)? {
+        let name: Vec<char> = path.file_name().map(|n| n.to_string_lossy().chars().collect()).unwrap_or_default();
+        if name.first() == Some(&'.') && pattern.first() != Some(&'.') {
+            continue;
+        }
+        if matches_component(&pattern, &name) {
+            expand(&path, rest, out)?;
+        }
+    }
+    Ok(())
+}
+
+// State test files named by `patterns`: files are taken as is, directories are searched
+// recursively for JSON files and glob patterns are expanded. The result is sorted and
+// every pattern has to match something
+pub fn discover(patterns: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for pattern in patterns {
+        let mut matched: Vec<PathBuf> = Vec::new();
+        if is_pattern(pattern) {
+            let absolute = pattern.starts_with('/');
+            let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+            let base = if absolute { PathBuf::from("/") } else { PathBuf::from(".") };
+            let mut expanded = Vec::new();
+            expand(&base, &components, &mut expanded)?;
+            for path in expanded {
+                let path = if absolute { path } else { path.strip_prefix(".").map(Path::to_path_buf).unwrap_or(path) };
+                if path.is_dir() {
+                    walk(&path, &mut matched)?;
+                } else {
+                    matched.push(path);
+                }
+            }
+        } else {
+            let path = PathBuf::from(pattern);
+            if path.is_dir() {
+                walk(&path, &mut matched)?;
+            } else if path.exists() {
+                matched.push(path);
+            } else {
+                return Err(Error::io(pattern, std::io::Error::from(std::io::ErrorKind::NotFound)));
+            }
+        }
+        if matched.is_empty() {
+            return Err(Error::invalid(format!("No state test files found for `{}`", pattern)));
+        }
+        files.extend(matched);
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+// Runs `f` on every item using `jobs` worker threads. `on_result` is called on the calling
+// thread, in item order, as soon as the result of an item and of all the previous ones are known
+pub fn run_parallel<T, R, F, C>(items: &[T], jobs: usize, f: F, mut on_result: C)
+where T: Sync, R: Send, F: Fn(&T) -> R + Sync, C: FnMut(usize, R) {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel::<(usize, R)>();
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            let sender = sender.clone();
+            let (next, f) = (&next, &f);
+            scope.spawn(move || loop {
+                let ix = next.fetch_add(1, Ordering::Relaxed);
+                if ix >= items.len() || sender.send((ix, f(&items[ix]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending: BTreeMap<usize, R> = BTreeMap::new();
+        let mut expected = 0;
+        for (ix, result) in receiver {
+            pending.insert(ix, result);
+            while let Some(result) = pending.remove(&expected) {
+                on_result(expected, result);
+                expected += 1;
+            }
+        }
+    });
+}
+
+// Outcome of a batch run, failed and errored cases are listed by name
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub errored: usize,
+    pub failures: Vec<String>,
+    pub errors: Vec<String>
+}
+
+impl Summary {
+    pub fn pass(&mut self) {
+        self.passed += 1;
+    }
+
+    pub fn fail(&mut self, case: impl Into<String>) {
+        self.failed += 1;
+        self.failures.push(case.into());
+    }
+
+    pub fn skip(&mut self, cases: usize) {
+        self.skipped += cases;
+    }
+
+    pub fn error(&mut self, case: impl Into<String>, e: &Error) {
+        self.errored += 1;
+        self.errors.push(format!("{}: {}", case.into(), e));
+    }
+
+    pub fn total(&self) -> usize {
+        self.passed + self.failed + self.skipped + self.errored
+    }
+
+    pub fn print(&self) {
+        if !self.failures.is_empty() {
+            println!("Failed:");
+            for case in &self.failures {
+                println!("\t{}", case);
+            }
+        }
+        if !self.errors.is_empty() {
+            println!("Errored:");
+            for case in &self.errors {
+                println!("\t{}", case);
+            }
+        }
+        println!("{} cases: {} passed, {} failed, {} skipped, {} errored", self.total(), self.passed, self.failed, self.skipped, self.errored);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        matches_component(&pattern, &name)
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("*.json", "add.json"));
+        assert!(matches("*.json", ".json"));
+        assert!(!matches("*.json", "add.yml"));
+        assert!(matches("st*Call*", "stCallCodes"));
+        assert!(matches("add?.json", "add1.json"));
+        assert!(!matches("add?.json", "add.json"));
+        assert!(matches("add", "add"));
+        assert!(!matches("add", "add1"));
+    }
+
+    #[test]
+    fn matches_classes() {
+        assert!(matches("test[0-9].json", "test7.json"));
+        assert!(!matches("test[0-9].json", "testa.json"));
+        assert!(matches("test[abc]", "testb"));
+        assert!(matches("test[!abc]", "testd"));
+        assert!(!matches("test[^abc]", "testa"));
+        assert!(!matches("test[0-9]", "test"));
+        // Without a closing bracket `[` is taken literally
+        assert!(matches("test[0", "test[0"));
+    }
+
+    // Fresh directory below the system temporary directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("t8n-batch-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn file(&self, path: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "{}").unwrap();
+            path
+        }
+
+        fn pattern(&self, pattern: &str) -> String {
+            format!("{}/{}", self.0.to_string_lossy(), pattern)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn discovers_files_directories_and_patterns() {
+        let dir = TempDir::new("discover");
+        let a = dir.file("stA/a.json");
+        let b = dir.file("stA/sub/b.json");
+        let c = dir.file("stB/c.json");
+        dir.file("stA/notes.txt");
+        dir.file("stA/.hidden/d.json");
+
+        assert_eq!(discover(&[dir.pattern("stA")]).unwrap(), vec![a.clone(), b.clone()]);
+        assert_eq!(discover(&[dir.pattern("stB/c.json")]).unwrap(), vec![c.clone()]);
+        assert_eq!(discover(&[dir.pattern("st*/*.json")]).unwrap(), vec![a.clone(), c.clone()]);
+        assert_eq!(discover(&[dir.pattern("**/*.json")]).unwrap(), vec![a.clone(), b.clone(), c.clone()]);
+        assert_eq!(discover(&[dir.pattern("stB"), dir.pattern("st[A]")]).unwrap(), vec![a, b, c]);
+
+        assert!(matches!(discover(&[dir.pattern("missing.json")]), Err(Error::Io { .. })));
+        assert!(matches!(discover(&[dir.pattern("*.yml")]), Err(Error::InvalidInput(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_follow_symlink_loops() {
+        let dir = TempDir::new("symlinks");
+        let a = dir.file("st/a.json");
+        std::os::unix::fs::symlink(&dir.0, dir.0.join("st/loop")).unwrap();
+
+        assert_eq!(discover(&[dir.pattern("st")]).unwrap(), vec![a.clone()]);
+        assert_eq!(discover(&[dir.pattern("**/*.json")]).unwrap(), vec![a]);
+    }
+
+    #[test]
+    fn run_parallel_reports_in_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let mut results = Vec::new();
+        run_parallel(&items, 4, |item| item * 2, |ix, result| results.push((ix, result)));
+        assert_eq!(results, items.iter().map(|i| (*i as usize, i * 2)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::context::{read_json, AccessListItem, Alloc, Authorization, Context, Env, ExecutionResult, Mismatch, Ommer, TransactionT8n, Withdrawal};
+use crate::error::Error;
+use crate::fork::HardFork;
+use crate::types::{empty_string_as_none, Address, Bytes, B256, U256};
+
+// Mining reward applied by t8n (--state.reward), -1 after the Merge disables it explicitly
+// instead of relying on the default of the tool
+fn block_reward(fork: HardFork) -> i64 {
+    if fork >= HardFork::Merge {
+        -1
+    } else if fork >= HardFork::Constantinople {
+        2_000_000_000_000_000_000
+    } else if fork >= HardFork::Byzantium {
+        3_000_000_000_000_000_000
+    } else {
+        5_000_000_000_000_000_000
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BlockHeader {
+    coinbase: Address,
+    difficulty: U256,
+    #[serde(rename="gasLimit")]
+    gas_limit: U256,
+    #[serde(rename="gasUsed")]
+    gas_used: U256,
+    hash: B256,
+    #[serde(rename="mixHash")]
+    mix_hash: B256,
+    number: U256,
+    #[serde(rename="parentHash")]
+    parent_hash: B256,
+    #[serde(rename="receiptTrie")]
+    receipt_trie: B256,
+    #[serde(rename="stateRoot")]
+    state_root: B256,
+    timestamp: U256,
+    #[serde(rename="transactionsTrie")]
+    transactions_trie: B256,
+    #[serde(rename="baseFeePerGas")]
+    base_fee_per_gas: Option<U256>,
+    #[serde(rename="blobGasUsed")]
+    blob_gas_used: Option<U256>,
+    #[serde(rename="excessBlobGas")]
+    excess_blob_gas: Option<U256>,
+    #[serde(rename="parentBeaconBlockRoot")]
+    parent_beacon_block_root: Option<B256>,
+    #[serde(rename="withdrawalsRoot")]
+    withdrawals_root: Option<B256>
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BlockTransaction {
+    data: Bytes,
+    #[serde(rename="gasLimit")]
+    gas_limit: U256,
+    #[serde(rename="gasPrice")]
+    gas_price: Option<U256>,
+    #[serde(rename="maxFeePerGas")]
+    max_fee_per_gas: Option<U256>,
+    #[serde(rename="maxPriorityFeePerGas")]
+    max_priority_fee_per_gas: Option<U256>,
+    #[serde(rename="accessList")]
+    access_list: Option<Vec<AccessListItem>>,
+    #[serde(rename="maxFeePerBlobGas")]
+    max_fee_per_blob_gas: Option<U256>,
+    #[serde(rename="blobVersionedHashes")]
+    blob_versioned_hashes: Option<Vec<B256>>,
+    #[serde(rename="authorizationList")]
+    authorization_list: Option<Vec<Authorization>>,
+    nonce: U256,
+    #[serde(deserialize_with="empty_string_as_none", default)]
+    to: Option<Address>,
+    value: U256,
+    v: U256,
+    r: U256,
+    s: U256,
+    #[serde(rename="chainId")]
+    chain_id: Option<U256>,
+    #[serde(rename="type")]
+    tx_type: Option<U256>
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct Block {
+    #[serde(rename="blockHeader")]
+    block_header: Option<BlockHeader>,
+    #[serde(default)]
+    transactions: Vec<BlockTransaction>,
+    #[serde(rename="expectException")]
+    expect_exception: Option<String>,
+    #[serde(rename="uncleHeaders", default)]
+    uncle_headers: Vec<BlockHeader>,
+    withdrawals: Option<Vec<Withdrawal>>
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct BlockchainTestContent {
+    blocks: Vec<Block>,
+    #[serde(rename="genesisBlockHeader")]
+    genesis_block_header: BlockHeader,
+    network: String,
+    pre: HashMap<Address, Alloc>,
+    #[serde(rename="postState")]
+    post_state: Option<HashMap<Address, Alloc>>,
+    #[serde(rename="postStateHash")]
+    post_state_hash: Option<B256>
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockchainVerification {
+    pub test_name: String,
+    pub network: String,
+    pub blocks: usize,
+    pub mismatches: Vec<Mismatch>
+}
+
+impl BlockchainVerification {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    pub fn print(&self) {
+        let case = format!("{} {} ({} blocks)", self.test_name, self.network, self.blocks);
+        if self.passed() {
+            println!("PASS {}", case);
+        } else {
+            println!("FAIL {}", case);
+            for m in &self.mismatches {
+                println!("\t{}: expected {}, got {}", m.field, m.expected, m.actual);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockchainTest {
+    pub name: String,
+    content: BlockchainTestContent
+}
+
+impl BlockchainTest {
+    // Loads every test of a BlockchainTests fixture, sorted by name
+    pub fn load(bt_name: &str) -> Result<Vec<BlockchainTest>, Error> {
+        let tests: HashMap<String, BlockchainTestContent> = read_json(bt_name)?;
+        let mut tests: Vec<BlockchainTest> = tests.into_iter()
+            .map(|(name, content)| BlockchainTest { name, content })
+            .collect();
+        tests.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(tests)
+    }
+
+    pub fn network(&self) -> &str {
+        self.content.network.as_str()
+    }
+
+    // Blocks expected to be valid, the ones carrying `expectException` are never imported
+    fn valid_blocks(&self) -> Vec<&BlockHeader> {
+        self.content.blocks.iter()
+            .filter(|b| b.expect_exception.is_none())
+            .filter_map(|b| b.block_header.as_ref())
+            .collect()
+    }
+
+    // Builds the context of the valid block `block_ix`, executed on top of `pre`
+    pub fn block_context(&self, block_ix: usize, pre: HashMap<Address, Alloc>, config: &Config) -> Result<Context, Error> {
+        let block = self.content.blocks.iter()
+            .filter(|b| b.expect_exception.is_none() && b.block_header.is_some())
+            .nth(block_ix)
+            .ok_or_else(|| Error::fixture(format!("{}: block {} not found", self.name, block_ix + 1)))?;
+        let header = block.block_header.as_ref().ok_or_else(|| Error::fixture(format!("{}: block {} has no header", self.name, block_ix + 1)))?;
+
+        let fork: HardFork = self.content.network.parse().map_err(|e| Error::fixture(format!("{}: {}", self.name, e)))?;
+        let active = fork.active(header.number.to_u64().unwrap_or(u64::MAX), header.timestamp.to_u64().unwrap_or(u64::MAX), header.difficulty.is_zero());
+
+        // Hashes of the genesis and of the valid blocks before this one
+        let block_hashes: BTreeMap<U256, B256> = std::iter::once(&self.content.genesis_block_header)
+            .chain(self.valid_blocks().into_iter().take(block_ix))
+            .map(|h| (h.number, h.hash))
+            .collect();
+        let ommers: Vec<Ommer> = block.uncle_headers.iter().map(|uncle| Ommer {
+            delta: (header.number.saturating_sub(uncle.number)).to_u64().unwrap_or_default(),
+            address: uncle.coinbase
+        }).collect();
+
+        let mut ctx = Context::with_config(config.clone());
+        ctx.config.hard_fork = fork;
+        ctx.reward = Some(block_reward(active));
+        ctx.alloc = pre;
+        ctx.env = Env {
+            current_base_fee: header.base_fee_per_gas,
+            current_coinbase: header.coinbase,
+            current_difficulty: Some(header.difficulty),
+            current_gas_limit: header.gas_limit,
+            current_number: header.number,
+            current_timestamp: header.timestamp,
+            // t8n treats the block as post-Merge whenever currentRandom is set
+            current_random: if header.difficulty.is_zero() { Some(header.mix_hash) } else { None },
+            previous_hash: header.parent_hash,
+            parent_base_fee: None,
+            parent_gas_used: None,
+            parent_gas_limit: None,
+            parent_difficulty: None,
+            parent_timestamp: None,
+            parent_uncle_hash: None,
+            block_hashes: Some(block_hashes),
+            ommers: if ommers.is_empty() { None } else { Some(ommers) },
+            current_excess_blob_gas: header.excess_blob_gas,
+            parent_excess_blob_gas: None,
+            parent_blob_gas_used: None,
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            withdrawals: block.withdrawals.clone()
+        };
+        ctx.txs = block.transactions.iter().map(|tx| TransactionT8n {
+            input: tx.data.clone(),
+            gas: tx.gas_limit,
+            gas_price: tx.gas_price,
+            max_fee_per_gas: tx.max_fee_per_gas,
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+            access_list: tx.access_list.clone(),
+            max_fee_per_blob_gas: tx.max_fee_per_blob_gas,
+            blob_versioned_hashes: tx.blob_versioned_hashes.clone(),
+            authorization_list: tx.authorization_list.clone(),
+            nonce: tx.nonce,
+            to: tx.to,
+            value: tx.value,
+            v: tx.v,
+            r: tx.r,
+            s: tx.s,
+            secret_key: None,
+            chain_id: tx.chain_id.unwrap_or_else(|| U256::from(1)),
+            tx_type: tx.tx_type
+        }).collect();
+        Ok(ctx)
+    }
+
+    fn verify_block(block_ix: usize, header: &BlockHeader, result: &ExecutionResult, mismatches: &mut Vec<Mismatch>) {
+        let checks = [
+            ("stateRoot", header.state_root, result.state_root),
+            ("transactionsTrie", header.transactions_trie, result.tx_root),
+            ("receiptTrie", header.receipt_trie, result.receipts_root)
+        ];
+        for (field, expected, actual) in checks {
+            if expected != actual {
+                mismatches.push(Mismatch { field: format!("block {} {}", block_ix + 1, field), expected: expected.to_string(), actual: actual.to_string() });
+            }
+        }
+        if header.gas_used != result.gas_used {
+            mismatches.push(Mismatch { field: format!("block {} gasUsed", block_ix + 1), expected: header.gas_used.to_string(), actual: result.gas_used.to_string() });
+        }
+        if let (Some(expected), Some(actual)) = (header.withdrawals_root, result.withdrawals_root) {
+            if expected != actual {
+                mismatches.push(Mismatch { field: format!("block {} withdrawalsRoot", block_ix + 1), expected: expected.to_string(), actual: actual.to_string() });
+            }
+        }
+        if let (Some(expected), Some(actual)) = (header.blob_gas_used, result.blob_gas_used) {
+            if expected != actual {
+                mismatches.push(Mismatch { field: format!("block {} blobGasUsed", block_ix + 1), expected: expected.to_string(), actual: actual.to_string() });
+            }
+        }
+        for rejected in &result.rejected {
+            mismatches.push(Mismatch { field: format!("block {} tx {}", block_ix + 1, rejected.index), expected: "included".to_string(), actual: rejected.error.clone() });
+        }
+    }
+
+    fn verify_post_state(&self, post: &HashMap<Address, Alloc>, mismatches: &mut Vec<Mismatch>) {
+        let expected_state = match &self.content.post_state {
+            Some(state) => state,
+            None => return
+        };
+
+        let mut addresses: Vec<&Address> = expected_state.keys().collect();
+        addresses.sort();
+        for address in addresses {
+            let expected = &expected_state[address];
+            let actual = match post.get(address) {
+                Some(actual) => actual,
+                None => {
+                    mismatches.push(Mismatch { field: address.to_string(), expected: "account".to_string(), actual: "missing".to_string() });
+                    continue;
+                }
+            };
+            if expected.balance != actual.balance {
+                mismatches.push(Mismatch { field: format!("{} balance", address), expected: expected.balance.to_string(), actual: actual.balance.to_string() });
+            }
+            if expected.nonce != actual.nonce {
+                mismatches.push(Mismatch { field: format!("{} nonce", address), expected: expected.nonce.to_string(), actual: actual.nonce.to_string() });
+            }
+            if expected.code != actual.code {
+                mismatches.push(Mismatch { field: format!("{} code", address), expected: expected.code.to_string(), actual: actual.code.to_string() });
+            }
+            let mut slots: Vec<&U256> = expected.storage.keys().chain(actual.storage.keys()).collect();
+            slots.sort();
+            slots.dedup();
+            for slot in slots {
+                let expected_value = expected.storage.get(slot).copied().unwrap_or_default();
+                let actual_value = actual.storage.get(slot).copied().unwrap_or_default();
+                if expected_value != actual_value {
+                    mismatches.push(Mismatch { field: format!("{} storage {}", address, slot), expected: expected_value.to_string(), actual: actual_value.to_string() });
+                }
+            }
+        }
+    }
+
+    // Executes the valid blocks one after the other, each one on top of the post state of
+    // the previous one, and checks the block headers and the final post state
+    pub fn run(&self, config: &Config) -> Result<BlockchainVerification, Error> {
+        let mut alloc = self.content.pre.clone();
+        let mut mismatches: Vec<Mismatch> = Vec::new();
+        let headers = self.valid_blocks();
+        let mut last_state_root = self.content.genesis_block_header.state_root;
+
+        for (block_ix, header) in headers.iter().enumerate() {
+            let ctx = self.block_context(block_ix, alloc, config)?;
+            let output = ctx.run()?;
+            BlockchainTest::verify_block(block_ix, header, &output.result, &mut mismatches);
+            last_state_root = output.result.state_root;
+            alloc = output.alloc;
+        }
+
+        if let Some(post_state_hash) = self.content.post_state_hash {
+            if post_state_hash != last_state_root {
+                mismatches.push(Mismatch { field: "postStateHash".to_string(), expected: post_state_hash.to_string(), actual: last_state_root.to_string() });
+            }
+        }
+        self.verify_post_state(&alloc, &mut mismatches);
+
+        Ok(BlockchainVerification {
+            test_name: self.name.clone(),
+            network: self.content.network.clone(),
+            blocks: headers.len(),
+            mismatches
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn hash(byte: u8) -> String {
+        format!("0x{}", hex::encode([byte; 32]))
+    }
+
+    fn header(number: u64, difficulty: u64, parent: u8) -> Value {
+        json!({
+            "coinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+            "difficulty": format!("0x{:x}", difficulty),
+            "gasLimit": "0x05f5e100",
+            "gasUsed": "0x5208",
+            "hash": hash(number as u8 + 1),
+            "mixHash": hash(0xaa),
+            "number": format!("0x{:x}", number),
+            "parentHash": hash(parent),
+            "receiptTrie": hash(0xb1),
+            "stateRoot": hash(0xb2),
+            "timestamp": format!("0x{:x}", 1000 + 12 * number),
+            "transactionsTrie": hash(0xb3)
+        })
+    }
+
+    // Genesis, a valid block 1, an invalid block and a valid block 2 with an ommer of block 1
+    fn fixture(network: &str, difficulty: u64) -> BlockchainTest {
+        let content = json!({
+            "network": network,
+            "genesisBlockHeader": header(0, difficulty, 0),
+            "blocks": [
+                {
+                    "blockHeader": header(1, difficulty, 1),
+                    "transactions": [{
+                        "data": "0x6001",
+                        "gasLimit": "0x5208",
+                        "gasPrice": "0x0a",
+                        "nonce": "0x00",
+                        "to": "0x0000000000000000000000000000000000000100",
+                        "value": "0x01",
+                        "v": "0x1b",
+                        "r": "0x01",
+                        "s": "0x02"
+                    }]
+                },
+                { "expectException": "TransactionException.INTRINSIC_GAS_TOO_LOW", "rlp": "0x00" },
+                {
+                    "blockHeader": header(2, difficulty, 2),
+                    "transactions": [{
+                        "data": "0x",
+                        "gasLimit": "0x5208",
+                        "maxFeePerGas": "0x0a",
+                        "maxPriorityFeePerGas": "0x01",
+                        "nonce": "0x01",
+                        "to": "",
+                        "value": "0x00",
+                        "v": "0x00",
+                        "r": "0x01",
+                        "s": "0x02",
+                        "chainId": "0x05",
+                        "type": "0x02"
+                    }],
+                    "uncleHeaders": [header(1, difficulty, 1)]
+                }
+            ],
+            "pre": {
+                "0x0000000000000000000000000000000000000100": { "balance": "0x0a", "code": "0x", "nonce": "0x00", "storage": {} }
+            },
+            "postState": {
+                "0x0000000000000000000000000000000000000100": { "balance": "0x0b", "code": "0x", "nonce": "0x00", "storage": { "0x01": "0x02" } },
+                "0x0000000000000000000000000000000000000200": { "balance": "0x00", "code": "0x", "nonce": "0x00", "storage": {} }
+            }
+        });
+        BlockchainTest { name: "fixture".to_string(), content: serde_json::from_value(content).unwrap() }
+    }
+
+    fn config() -> Config {
+        Config { work_dir: String::new(), t8n: String::new(), evm: String::new(), hard_fork: HardFork::Merge }
+    }
+
+    #[test]
+    fn block_context_maps_the_header_to_the_env() {
+        let test = fixture("Byzantium", 0x020000);
+        let ctx = test.block_context(1, test.content.pre.clone(), &config()).unwrap();
+        assert_eq!(ctx.config.hard_fork, HardFork::Byzantium);
+        assert_eq!(ctx.alloc.len(), 1);
+        let env = &ctx.env;
+        assert_eq!(env.current_number, U256::from(2));
+        assert_eq!(env.current_timestamp, U256::from(1024));
+        assert_eq!(env.current_difficulty, Some(U256::from(0x020000)));
+        assert_eq!(env.current_random, None);
+        assert_eq!(env.previous_hash.to_string(), hash(2));
+        // Genesis and block 1, the invalid block is not part of the chain
+        let block_hashes: Vec<(U256, String)> = env.block_hashes.iter().flatten().map(|(n, h)| (*n, h.to_string())).collect();
+        assert_eq!(block_hashes, vec![(U256::ZERO, hash(1)), (U256::from(1), hash(2))]);
+    }
+
+    #[test]
+    fn block_context_after_the_merge_sets_the_random_value() {
+        let test = fixture("Shanghai", 0);
+        let ctx = test.block_context(0, test.content.pre.clone(), &config()).unwrap();
+        assert_eq!(ctx.env.current_random.map(|r| r.to_string()), Some(hash(0xaa)));
+        assert_eq!(ctx.reward, Some(-1));
+        assert!(ctx.env.ommers.is_none());
+    }
+
+    #[test]
+    fn block_context_maps_transactions() {
+        let test = fixture("London", 0x020000);
+        let legacy = &test.block_context(0, HashMap::new(), &config()).unwrap().txs[0];
+        assert_eq!(legacy.input, Bytes(vec![0x60, 0x01]));
+        assert_eq!(legacy.gas, U256::from(21000));
+        assert_eq!(legacy.gas_price, Some(U256::from(10)));
+        assert_eq!(legacy.to, Some(Address::from_low_u64(0x100)));
+        assert_eq!((legacy.v, legacy.r, legacy.s), (U256::from(27), U256::from(1), U256::from(2)));
+        assert_eq!(legacy.chain_id, U256::from(1));
+        assert_eq!(legacy.tx_type(), 0);
+        assert!(!legacy.has_secret_key());
+
+        let dynamic_fee = &test.block_context(1, HashMap::new(), &config()).unwrap().txs[0];
+        assert_eq!(dynamic_fee.to, None);
+        assert_eq!(dynamic_fee.max_fee_per_gas, Some(U256::from(10)));
+        assert_eq!(dynamic_fee.max_priority_fee_per_gas, Some(U256::from(1)));
+        assert_eq!(dynamic_fee.chain_id, U256::from(5));
+        assert_eq!(dynamic_fee.tx_type(), 2);
+
+        assert!(test.block_context(2, HashMap::new(), &config()).is_err());
+    }
+
+    #[test]
+    fn block_reward_by_fork() {
+        assert_eq!(block_reward(HardFork::Frontier), 5_000_000_000_000_000_000);
+        assert_eq!(block_reward(HardFork::EIP158), 5_000_000_000_000_000_000);
+        assert_eq!(block_reward(HardFork::Byzantium), 3_000_000_000_000_000_000);
+        assert_eq!(block_reward(HardFork::Constantinople), 2_000_000_000_000_000_000);
+        assert_eq!(block_reward(HardFork::GrayGlacier), 2_000_000_000_000_000_000);
+        assert_eq!(block_reward(HardFork::Merge), -1);
+        assert_eq!(block_reward(HardFork::Prague), -1);
+
+        // Transition forks use the reward of the fork active at the block
+        let test = fixture("EIP158ToByzantiumAt5", 0x020000);
+        assert_eq!(test.block_context(0, HashMap::new(), &config()).unwrap().reward, Some(5_000_000_000_000_000_000));
+    }
+
+    #[test]
+    fn ommers_are_given_by_their_distance() {
+        let test = fixture("Byzantium", 0x020000);
+        let ctx = test.block_context(1, HashMap::new(), &config()).unwrap();
+        let ommers = ctx.env.ommers.unwrap();
+        assert_eq!(ommers.len(), 1);
+        assert_eq!(ommers[0].delta, 1);
+        assert_eq!(ommers[0].address, "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba".parse().unwrap());
+        assert!(test.block_context(0, HashMap::new(), &config()).unwrap().env.ommers.is_none());
+    }
+
+    #[test]
+    fn verify_block_reports_mismatches() {
+        let test = fixture("Byzantium", 0x020000);
+        let header = test.valid_blocks()[0].clone();
+        let result: ExecutionResult = serde_json::from_value(json!({
+            "stateRoot": hash(0xb2),
+            "txRoot": hash(0xb3),
+            "receiptsRoot": hash(0xc1),
+            "logsHash": hash(0),
+            "logsBloom": "0x00",
+            "gasUsed": "0x5208",
+            "rejected": [{ "index": 1, "error": "nonce too low" }]
+        })).unwrap();
+        let mut mismatches = Vec::new();
+        BlockchainTest::verify_block(0, &header, &result, &mut mismatches);
+        let fields: Vec<&str> = mismatches.iter().map(|m| m.field.as_str()).collect();
+        assert_eq!(fields, vec!["block 1 receiptTrie", "block 1 tx 1"]);
+        assert_eq!(mismatches[0].expected, hash(0xb1));
+        assert_eq!(mismatches[1].actual, "nonce too low");
+    }
+
+    #[test]
+    fn verify_post_state_reports_mismatches() {
+        let test = fixture("Byzantium", 0x020000);
+        let mut post = test.content.pre.clone();
+        post.get_mut(&Address::from_low_u64(0x100)).unwrap().storage.insert(U256::from(3), U256::from(4));
+        let mut mismatches = Vec::new();
+        test.verify_post_state(&post, &mut mismatches);
+        let fields: Vec<&str> = mismatches.iter().map(|m| m.field.as_str()).collect();
+        assert_eq!(fields, vec![
+            "0x0000000000000000000000000000000000000100 balance",
+            "0x0000000000000000000000000000000000000100 storage 0x1",
+            "0x0000000000000000000000000000000000000100 storage 0x3",
+            "0x0000000000000000000000000000000000000200"
+        ]);
+        assert_eq!((mismatches[1].expected.as_str(), mismatches[1].actual.as_str()), ("0x2", "0x0"));
+        assert_eq!(mismatches[3].actual, "missing");
+
+        let mut matching = Vec::new();
+        test.verify_post_state(&test.content.post_state.clone().unwrap(), &mut matching);
+        assert!(matching.is_empty());
+    }
+}
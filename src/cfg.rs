@@ -0,0 +1,372 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::context::{Context, Trace};
+use crate::disasm::{self, Instruction};
+use crate::error::Error;
+use crate::fork::HardFork;
+use crate::types::Address;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    // Execution continues with the next instruction, including a JUMPI not taken
+    Fallthrough,
+    // JUMP or taken JUMPI whose target is pushed right before it
+    Jump,
+    // Target only known at runtime, taken from a trace
+    Dynamic
+}
+
+// Edges connect blocks by the pc of their first instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    // Ends in a JUMP or JUMPI whose target is not a constant pushed right before it
+    pub dynamic_jump: bool,
+    // Constant jump target that is not a JUMPDEST, the jump always fails
+    pub invalid_target: Option<usize>
+}
+
+impl BasicBlock {
+    pub fn last(&self) -> &Instruction {
+        &self.instructions[self.instructions.len() - 1]
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        let last = self.last();
+        self.start <= pc && pc <= last.pc + last.immediate.len()
+    }
+}
+
+// Part of a CFG executed in a run
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    pub blocks: BTreeSet<usize>,
+    pub edges: BTreeSet<Edge>
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>
+}
+
+const JUMP: u8 = 0x56;
+const JUMPI: u8 = 0x57;
+
+// Instructions after which execution never continues with the next one
+fn ends_block(instruction: &Instruction) -> bool {
+    match instruction.opcode {
+        Some(_) => matches!(instruction.byte, 0x00 | JUMP | JUMPI | 0xf3 | 0xfd | 0xfe | 0xff) || instruction.is_truncated(),
+        None => true
+    }
+}
+
+fn falls_through(instruction: &Instruction) -> bool {
+    instruction.byte == JUMPI || !ends_block(instruction)
+}
+
+// Value pushed by a PUSH instruction, None when it does not fit a pc
+fn pushed_target(instruction: &Instruction) -> Option<usize> {
+    if !instruction.opcode.is_some_and(|o| o.is_push()) || instruction.is_truncated() {
+        return None;
+    }
+    let first = instruction.immediate.iter().position(|b| *b != 0).unwrap_or(instruction.immediate.len());
+    let significant = &instruction.immediate[first..];
+    if significant.len() > 8 {
+        return None;
+    }
+    Some(significant.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64) as usize)
+}
+
+impl Cfg {
+    // Splits the code into basic blocks, starting a block at every JUMPDEST and after every
+    // instruction that ends one. Jumps to a constant pushed right before the JUMP or JUMPI
+    // are resolved, any other jump is flagged as dynamic
+    pub fn build(code: &[u8], fork: HardFork) -> Cfg {
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut current: Vec<Instruction> = Vec::new();
+        for instruction in disasm::disassemble(code, fork) {
+            if instruction.is_jumpdest() && !current.is_empty() {
+                blocks.push(BasicBlock { start: current[0].pc, instructions: std::mem::take(&mut current), dynamic_jump: false, invalid_target: None });
+            }
+            let ends = ends_block(&instruction);
+            current.push(instruction);
+            if ends {
+                blocks.push(BasicBlock { start: current[0].pc, instructions: std::mem::take(&mut current), dynamic_jump: false, invalid_target: None });
+            }
+        }
+        if !current.is_empty() {
+            blocks.push(BasicBlock { start: current[0].pc, instructions: current, dynamic_jump: false, invalid_target: None });
+        }
+
+        let jumpdests: BTreeSet<usize> = blocks.iter().filter(|b| b.instructions[0].is_jumpdest()).map(|b| b.start).collect();
+        let mut edges: Vec<Edge> = Vec::new();
+        for ix in 0..blocks.len() {
+            let next = blocks.get(ix + 1).map(|b| b.start);
+            let block = &mut blocks[ix];
+            let last = block.last();
+            if last.byte == JUMP || last.byte == JUMPI {
+                let target = block.instructions.len().checked_sub(2).and_then(|prev| pushed_target(&block.instructions[prev]));
+                match target {
+                    Some(target) if jumpdests.contains(&target) => edges.push(Edge { from: block.start, to: target, kind: EdgeKind::Jump }),
+                    Some(target) => block.invalid_target = Some(target),
+                    None => block.dynamic_jump = true
+                }
+            }
+            if let Some(next) = next.filter(|_| falls_through(block.last())) {
+                edges.push(Edge { from: block.start, to: next, kind: EdgeKind::Fallthrough });
+            }
+        }
+        edges.sort();
+        edges.dedup();
+        Cfg { blocks, edges }
+    }
+
+    // Start of the block holding `pc`
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        let ix = self.blocks.partition_point(|b| b.start <= pc).checked_sub(1)?;
+        self.blocks.get(ix).filter(|b| b.contains(pc)).map(|b| b.start)
+    }
+
+    // Blocks and edges run through by consecutive pcs of the same call frame, see
+    // `Context::code_transitions`. Jumps that are not static edges are recorded as dynamic ones
+    pub fn coverage(&self, transitions: &[(usize, usize)]) -> Coverage {
+        let mut coverage = Coverage::default();
+        for (from_pc, to_pc) in transitions {
+            let (from, to) = match (self.block_at(*from_pc), self.block_at(*to_pc)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue
+            };
+            coverage.blocks.insert(from);
+            coverage.blocks.insert(to);
+            if *to_pc != to || from_pc == to_pc {
+                continue;
+            }
+            let edge = self.edges.iter().find(|e| e.from == from && e.to == to).copied()
+                .unwrap_or(Edge { from, to, kind: EdgeKind::Dynamic });
+            coverage.edges.insert(edge);
+        }
+        coverage
+    }
+
+    // Graphviz DOT graph, one node per block listing its instructions. Executed blocks and
+    // edges are highlighted, dynamic jumps and invalid targets are marked
+    pub fn to_dot(&self, coverage: Option<&Coverage>) -> String {
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph cfg {{");
+        let _ = writeln!(dot, "  node [shape=box fontname=\"monospace\"];");
+        for block in &self.blocks {
+            let mut label: String = block.instructions.iter()
+                .map(|i| escape(i.to_string().trim_start_matches("=>").trim_start()) + "\\l")
+                .collect();
+            if block.dynamic_jump {
+                label.push_str("(dynamic jump)\\l");
+            }
+            if let Some(target) = block.invalid_target {
+                label.push_str(&format!("(invalid jump target {})\\l", target));
+            }
+            let mut attributes = vec![format!("label=\"{}\"", label)];
+            if block.dynamic_jump || block.invalid_target.is_some() {
+                attributes.push("color=orange".to_string());
+            }
+            if coverage.is_some_and(|c| c.blocks.contains(&block.start)) {
+                attributes.push("style=filled fillcolor=lightyellow".to_string());
+            }
+            let _ = writeln!(dot, "  b{} [{}];", block.start, attributes.join(" "));
+        }
+
+        let dynamic: Vec<&Edge> = coverage.iter().flat_map(|c| c.edges.iter()).filter(|e| e.kind == EdgeKind::Dynamic).collect();
+        for edge in self.edges.iter().chain(dynamic) {
+            let mut attributes: Vec<&str> = Vec::new();
+            match edge.kind {
+                EdgeKind::Fallthrough => {},
+                EdgeKind::Jump => attributes.push("label=\"jump\""),
+                EdgeKind::Dynamic => attributes.push("label=\"dynamic\" style=dashed")
+            }
+            if coverage.is_some_and(|c| c.edges.contains(edge)) {
+                attributes.push("color=red penwidth=2");
+            }
+            if attributes.is_empty() {
+                let _ = writeln!(dot, "  b{} -> b{};", edge.from, edge.to);
+            } else {
+                let _ = writeln!(dot, "  b{} -> b{} [{}];", edge.from, edge.to, attributes.join(" "));
+            }
+        }
+        let _ = writeln!(dot, "}}");
+        dot
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+const CALL: u8 = 0xf1;
+const CALLCODE: u8 = 0xf2;
+const DELEGATECALL: u8 = 0xf4;
+const STATICCALL: u8 = 0xfa;
+
+impl Context {
+    // DOT graph of the code of `address`, with the code executed in `traces` highlighted
+    pub fn cfg_dot(&self, address: Address, fork: HardFork, traces: Option<&[Trace]>) -> Result<String, Error> {
+        let account = self.alloc.get(&address).ok_or_else(|| Error::invalid(format!("Address {} not found", address)))?;
+        if account.code.is_empty() {
+            return Err(Error::invalid(format!("Address {} has no code", address)));
+        }
+        if let Some(target) = account.delegation() {
+            return Err(Error::invalid(format!("Address {} is delegated to {}", address, target)));
+        }
+        let cfg = account.cfg(fork);
+        let coverage = traces.map(|traces| cfg.coverage(&self.code_transitions(traces, address)));
+        Ok(cfg.to_dot(coverage.as_ref()))
+    }
+
+    // Account whose code runs when `address` is called, following EIP-7702 delegations
+    fn code_owner(&self, address: Address) -> Address {
+        self.alloc.get(&address).and_then(|a| a.delegation()).unwrap_or(address)
+    }
+
+    // Consecutive pcs executed with the code of `address` in the traces of a run of this
+    // context. Call frames are followed through CALL, CALLCODE, DELEGATECALL and STATICCALL,
+    // frames entered with CREATE run init code and are ignored
+    pub fn code_transitions(&self, traces: &[Trace], address: Address) -> Vec<(usize, usize)> {
+        let mut transitions: Vec<(usize, usize)> = Vec::new();
+        for trace in traces {
+            let to = self.txs.get(trace.tx_index).and_then(|tx| tx.to);
+            // Code owner and last pc of every frame on the call stack
+            let mut frames: Vec<(Option<Address>, Option<usize>)> = Vec::new();
+            // Account called by the previous step, None for the first step of the trace
+            let mut previous: Option<Option<Address>> = None;
+            for step in &trace.steps {
+                let depth = step.depth.max(1) as usize;
+                if depth > frames.len() {
+                    let owner = match previous {
+                        None => to.map(|to| self.code_owner(to)),
+                        Some(callee) => callee.map(|callee| self.code_owner(callee))
+                    };
+                    frames.resize(depth - 1, (None, None));
+                    frames.push((owner, None));
+                } else {
+                    frames.truncate(depth);
+                }
+                let frame = &mut frames[depth - 1];
+                if frame.0 == Some(address) {
+                    if let Some(last) = frame.1 {
+                        transitions.push((last, step.pc as usize));
+                    }
+                    frame.1 = Some(step.pc as usize);
+                }
+                let callee = match step.op {
+                    CALL | CALLCODE | DELEGATECALL | STATICCALL if step.stack.len() >= 2 => {
+                        let word = step.stack[step.stack.len() - 2].to_be_bytes();
+                        let mut callee = [0u8; 20];
+                        callee.copy_from_slice(&word[12..]);
+                        Some(Address(callee))
+                    },
+                    _ => None
+                };
+                previous = Some(callee);
+            }
+        }
+        transitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::context::{Alloc, TraceStep, TransactionT8n};
+    use crate::types::Bytes;
+
+    // 0: PUSH1 4, JUMP          static jump
+    // 3: STOP                   unreachable
+    // 4: JUMPDEST, PUSH1 1, PUSH1 13, JUMPI
+    // 10: CALLDATASIZE, JUMP    dynamic jump
+    // 12: STOP
+    // 13: JUMPDEST, PUSH1 13, JUMP   static self-loop
+    // 17: PUSH1 0x20, JUMP      invalid target
+    const CODE: [u8; 20] = [
+        0x60, 0x04, 0x56, 0x00, 0x5b, 0x60, 0x01, 0x60, 0x0d, 0x57,
+        0x36, 0x56, 0x00, 0x5b, 0x60, 0x0d, 0x56, 0x60, 0x20, 0x56
+    ];
+
+    fn edge(from: usize, to: usize, kind: EdgeKind) -> Edge {
+        Edge { from, to, kind }
+    }
+
+    // Pcs executed by CODE: through the dynamic jump to 13, then around the self-loop once
+    const PCS: [usize; 12] = [0, 2, 4, 5, 7, 9, 10, 11, 13, 14, 16, 13];
+
+    #[test]
+    fn resolves_static_jumps() {
+        let cfg = Cfg::build(&CODE, HardFork::Cancun);
+        let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+        assert_eq!(starts, vec![0, 3, 4, 10, 12, 13, 17]);
+        assert_eq!(cfg.edges, vec![
+            edge(0, 4, EdgeKind::Jump),
+            edge(4, 10, EdgeKind::Fallthrough),
+            edge(4, 13, EdgeKind::Jump),
+            edge(13, 13, EdgeKind::Jump)
+        ]);
+        let dynamic: Vec<usize> = cfg.blocks.iter().filter(|b| b.dynamic_jump).map(|b| b.start).collect();
+        assert_eq!(dynamic, vec![10]);
+        assert_eq!(cfg.blocks[6].invalid_target, Some(0x20));
+        assert!(cfg.blocks[..6].iter().all(|b| b.invalid_target.is_none()));
+
+        assert_eq!(cfg.block_at(1), Some(0));
+        assert_eq!(cfg.block_at(8), Some(4));
+        assert_eq!(cfg.block_at(19), Some(17));
+        assert_eq!(cfg.block_at(20), None);
+    }
+
+    #[test]
+    fn records_executed_edges() {
+        let cfg = Cfg::build(&CODE, HardFork::Cancun);
+        let transitions: Vec<(usize, usize)> = PCS.windows(2).map(|w| (w[0], w[1])).collect();
+        let coverage = cfg.coverage(&transitions);
+        assert_eq!(coverage.blocks.iter().copied().collect::<Vec<usize>>(), vec![0, 4, 10, 13]);
+        assert_eq!(coverage.edges.iter().copied().collect::<Vec<Edge>>(), vec![
+            edge(0, 4, EdgeKind::Jump),
+            edge(4, 10, EdgeKind::Fallthrough),
+            edge(10, 13, EdgeKind::Dynamic),
+            edge(13, 13, EdgeKind::Jump)
+        ]);
+
+        let dot = cfg.to_dot(Some(&coverage));
+        assert!(dot.contains("b10 -> b13 [label=\"dynamic\" style=dashed color=red penwidth=2];"), "{}", dot);
+        assert!(dot.contains("b13 -> b13 [label=\"jump\" color=red penwidth=2];"), "{}", dot);
+        assert!(dot.contains("b4 -> b13 [label=\"jump\"];"), "{}", dot);
+    }
+
+    #[test]
+    fn records_coverage_from_traces() {
+        let mut ctx = Context::with_config(Config { work_dir: String::new(), t8n: String::new(), evm: String::new(), hard_fork: HardFork::Cancun });
+        let address = Address::from_low_u64(0x100);
+        ctx.alloc.insert(address, Alloc { code: Bytes(CODE.to_vec()), ..Alloc::default() });
+        ctx.txs.push(TransactionT8n { to: Some(address), ..TransactionT8n::default() });
+
+        let steps: Vec<TraceStep> = PCS.iter()
+            .map(|pc| serde_json::from_value(serde_json::json!({ "pc": pc, "op": CODE[*pc], "gas": 100_000, "gasCost": 3, "depth": 1 })).unwrap())
+            .collect();
+        let traces = [Trace { tx_index: 0, tx_hash: String::new(), steps, summary: None }];
+        let transitions = ctx.code_transitions(&traces, address);
+        assert_eq!(transitions.len(), PCS.len() - 1);
+        assert!(ctx.code_transitions(&traces, Address::from_low_u64(0x200)).is_empty());
+
+        let coverage = ctx.alloc[&address].cfg(HardFork::Cancun).coverage(&transitions);
+        assert!(coverage.edges.contains(&edge(13, 13, EdgeKind::Jump)));
+        assert!(coverage.edges.contains(&edge(10, 13, EdgeKind::Dynamic)));
+        let dot = ctx.cfg_dot(address, HardFork::Cancun, Some(&traces)).unwrap();
+        assert!(dot.contains("b13 -> b13 [label=\"jump\" color=red penwidth=2];"), "{}", dot);
+    }
+}
@@ -0,0 +1,94 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::Helper;
+
+use crate::context::Context;
+use crate::fork::HardFork;
+use crate::repl::COMMANDS;
+
+// What a command expects at a given argument position
+enum Argument {
+    Address,
+    TxIndex,
+    Fork,
+    Path,
+    Other
+}
+
+fn argument(command: &str, position: usize) -> Argument {
+    match (command, position) {
+        ("dir" | "extract" | "t8n" | "evm" | "save" | "load", 0) | ("tracediff" | "diff-run", 0 | 1) | ("cfg", 1) => Argument::Path,
+        ("hf", 0) | ("code", 1) | ("cfg", 2) => Argument::Fork,
+        ("code" | "cfg" | "addcode" | "addeof" | "env.add.withdrawal" | "env.add.ommer", 0) => Argument::Address,
+        ("tx.set.sender" | "tx.set.receiver" | "tx.add.accesslist", 1) | ("tx.add.authorization", 1 | 2) => Argument::Address,
+        (_, 0) if command.starts_with("tx.") && command != "tx.new" => Argument::TxIndex,
+        _ => Argument::Other
+    }
+}
+
+fn candidates<'a>(values: impl Iterator<Item = &'a str>, prefix: &str) -> Vec<Pair> {
+    let prefix = prefix.to_ascii_lowercase();
+    values.filter(|v| v.to_ascii_lowercase().starts_with(&prefix))
+        .map(|v| Pair { display: v.to_string(), replacement: v.to_string() })
+        .collect()
+}
+
+// Tab completion of the REPL: command names first, then addresses of the allocation,
+// transaction indexes, fork names or file paths depending on the command argument.
+// The context is copied with `update` before reading every line
+#[derive(Default)]
+pub struct ReplHelper {
+    addresses: Vec<String>,
+    txs: usize,
+    files: FilenameCompleter
+}
+
+impl ReplHelper {
+    pub fn new() -> ReplHelper {
+        ReplHelper::default()
+    }
+
+    pub fn update(&mut self, ctx: &Context) {
+        self.addresses = ctx.alloc.keys().map(|a| a.to_string()).collect();
+        self.addresses.sort();
+        self.txs = ctx.txs.len();
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map(|ix| ix + 1).unwrap_or(0);
+        let word = &before[start..];
+        let mut words = before[..start].split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok((start, candidates(COMMANDS.iter().copied(), word)))
+        };
+        let found = match argument(command, words.count()) {
+            Argument::Path => return self.files.complete(line, pos, ctx),
+            Argument::Address => candidates(self.addresses.iter().map(String::as_str), word),
+            Argument::TxIndex => {
+                let indexes: Vec<String> = (0..self.txs).map(|ix| ix.to_string()).collect();
+                candidates(indexes.iter().map(String::as_str), word)
+            },
+            Argument::Fork => candidates(HardFork::ALL.iter().map(|f| f.name()), word),
+            Argument::Other => Vec::new()
+        };
+        Ok((start, found))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
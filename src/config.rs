@@ -1,70 +1,83 @@
 use std::fs;
-use std::io::Write;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
+use crate::fork::HardFork;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
     pub work_dir: String,
     pub t8n: String,
     pub evm: String,
-    pub hard_fork: String
+    pub hard_fork: HardFork
 }
 
-impl Config {
-    pub fn save(&self) -> bool {
-        let home_dir = home::home_dir().expect("Cannot determine HOME directory");
-        let config_file_path = String::from(home_dir.to_str().unwrap()) + "/.t8n-repl.json";
-        let config_str = serde_json::to_string(&self).unwrap();
-        let mut file = fs::File::create(config_file_path).unwrap();
-        if file.write(config_str.as_bytes()).is_err() {
-            return false;
-        } else {
-            return true;
+fn config_file_path() -> Result<PathBuf, Error> {
+    let home_dir = home::home_dir().ok_or_else(|| Error::invalid("Cannot determine HOME directory"))?;
+    Ok(home_dir.join(".t8n-repl.json"))
+}
+
+// REPL command history, next to the configuration file
+pub fn history_file_path() -> Result<PathBuf, Error> {
+    Ok(config_file_path()?.with_extension("history"))
+}
+
+// Same as `Config::load`, falling back to the built-in defaults if the configuration cannot be used
+impl Default for Config {
+    fn default() -> Config {
+        match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Warning: using default configuration, {}", e);
+                Config::builtin()
+            }
         }
     }
+}
 
-    pub fn default() -> Config {
-        let home_dir = home::home_dir().expect("Cannot determine HOME directory");
-        let config_file_path = String::from(home_dir.to_str().unwrap()) + "/.t8n-repl.json";
-        let config_file = fs::File::open(config_file_path.as_str());
-        if config_file.is_ok() {
-            let config: Config = serde_json::from_reader(BufReader::new(config_file.unwrap())).unwrap();
-            return config;
-        } else {
-            let home_dir = home::home_dir().expect("Cannot determine HOME directory");
-            let default_working_dir = String::from(home_dir.to_str().unwrap()) + "/t8n-repl";
+impl Config {
+    pub fn save(&self) -> Result<(), Error> {
+        let config_file_path = config_file_path()?;
+        let path = config_file_path.to_string_lossy();
+        let config_str = serde_json::to_string(&self).map_err(|e| Error::json(&path, e))?;
+        fs::write(&config_file_path, config_str).map_err(|e| Error::io(&path, e))
+    }
 
-            let working_dir_path = Path::new(default_working_dir.as_str()); 
-            if ! working_dir_path.exists() {
-                fs::create_dir(working_dir_path).expect("Error creating default working directory");
-            }
-            let config = Config {
-               work_dir : working_dir_path.to_str().unwrap().to_string(),
-               t8n: String::from("/bin/evm"),
-               evm : String::from(""),
-               hard_fork: String::from("Merge")
-            };
+    // Reads ~/.t8n-repl.json, creating it and the default working directory on first use
+    pub fn load() -> Result<Config, Error> {
+        let config_file_path = config_file_path()?;
+        let path = config_file_path.to_string_lossy();
+        if config_file_path.exists() {
+            let config_file = fs::File::open(&config_file_path).map_err(|e| Error::io(&path, e))?;
+            return serde_json::from_reader(BufReader::new(config_file)).map_err(|e| Error::json(&path, e));
+        }
 
-            config.save();
-            return config;
+        let config = Config::builtin();
+        let working_dir_path = Path::new(config.work_dir.as_str());
+        if !working_dir_path.exists() {
+            fs::create_dir(working_dir_path).map_err(|e| Error::io(config.work_dir.as_str(), e))?;
         }
+        config.save()?;
+        Ok(config)
     }
 
-    pub fn set_work_dir(&mut self, wdir_path : &str) {
-        let home_dir = home::home_dir().expect("Cannot determine HOME directory");
-        let config_file_path = String::from(home_dir.to_str().unwrap()) + "/.t8n-repl.json";
+    fn builtin() -> Config {
+        let default_working_dir = home::home_dir()
+            .map(|home_dir| home_dir.join("t8n-repl"))
+            .unwrap_or_else(|| PathBuf::from("t8n-repl"));
+        Config {
+            work_dir : default_working_dir.to_string_lossy().to_string(),
+            t8n: String::from("/bin/evm"),
+            evm : String::from(""),
+            hard_fork: HardFork::default()
+        }
+    }
 
+    pub fn set_work_dir(&mut self, wdir_path : &str) -> Result<(), Error> {
         self.work_dir = wdir_path.to_string();
         println!("Writing {} as default directory", wdir_path);
-        if self.save() {
-            println!("Configuration saved!");
-        } else {
-            println!("Error saving configuration");
-        }
+        self.save()
     }
 }
-
-
@@ -1,388 +1,1437 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::BufReader;
-use std::io::Write;
-use std::io;
-use std::fs::{self, DirEntry};
+use std::fs;
 use std::path::Path;
 use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
 use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
 
+use crate::cfg::Cfg;
 use crate::config::Config;
+use crate::crypto;
+use crate::disasm;
+use crate::eof::{self, Container, EofError};
+use crate::error::Error;
+use crate::fork::HardFork;
+use crate::rlp;
+use crate::types::{empty_string_as_none, Address, Bytes, B256, U256};
 
 // utils
-fn hex_remove_leading_zero(hex: String) -> String {
-  if hex.starts_with("0x0") && hex.len() > 3 {
-    String::from("0x") + &hex[3..]
-  } else {
-    hex
-  }
+pub(crate) fn read_json<T: DeserializeOwned>(fname: &str) -> Result<T, Error> {
+    let file = fs::File::open(fname).map_err(|e| Error::io(fname, e))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| Error::json(fname, e))
+}
+
+pub(crate) fn write_json<T: Serialize>(fname: &str, value: &T) -> Result<(), Error> {
+    let json = serde_json::to_string(value).map_err(|e| Error::json(fname, e))?;
+    fs::write(fname, json).map_err(|e| Error::io(fname, e))
+}
+
+// EIP-2930 access list entry
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: Address,
+    #[serde(rename="storageKeys", default)]
+    pub storage_keys: Vec<B256>
+}
+
+// EIP-7702 authorization: `authority` lets its account run the code of `address`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Authorization {
+    #[serde(rename="chainId")]
+    pub chain_id: U256,
+    pub address: Address,
+    pub nonce: U256,
+    #[serde(rename="yParity")]
+    pub y_parity: U256,
+    pub r: U256,
+    pub s: U256
+}
+
+impl Authorization {
+    // Signs keccak(0x05 || rlp([chain_id, address, nonce])) with the authority secret key
+    pub fn sign(chain_id: U256, address: Address, nonce: U256, secret_key: &B256) -> Result<Authorization, Error> {
+        let mut payload = vec![0x05];
+        payload.extend(rlp::encode_list(&[
+            rlp::encode_uint(&chain_id.to_be_bytes()), rlp::encode_bytes(&address.0), rlp::encode_uint(&nonce.to_be_bytes())
+        ]));
+        let (y_parity, r, s) = crypto::sign_hash(secret_key, &crypto::keccak256(&payload))?;
+        Ok(Authorization { chain_id, address, nonce, y_parity: U256::from(y_parity), r, s })
+    }
+
+    fn rlp_encode(&self) -> Vec<u8> {
+        let uint = |v: &U256| rlp::encode_uint(&v.to_be_bytes());
+        rlp::encode_list(&[
+            uint(&self.chain_id), rlp::encode_bytes(&self.address.0), uint(&self.nonce), uint(&self.y_parity), uint(&self.r), uint(&self.s)
+        ])
+    }
 }
 
 #[serde_with::skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionT8n {
-    input : String,
-    gas: String,
+    pub(crate) input : Bytes,
+    pub(crate) gas: U256,
+    // Legacy and type 0x1 transactions only
     #[serde(rename="gasPrice")]
-    gas_price: String,
-    nonce: String,
-    to: Option<String>,
-    value: String,
-    v: String,
-    r: String,
-    s: String,
+    pub(crate) gas_price: Option<U256>,
+    // Type 0x2 transactions only
+    #[serde(rename="maxFeePerGas")]
+    pub(crate) max_fee_per_gas: Option<U256>,
+    #[serde(rename="maxPriorityFeePerGas")]
+    pub(crate) max_priority_fee_per_gas: Option<U256>,
+    #[serde(rename="accessList")]
+    pub(crate) access_list: Option<Vec<AccessListItem>>,
+    // Type 0x3 (blob) transactions only
+    #[serde(rename="maxFeePerBlobGas")]
+    pub(crate) max_fee_per_blob_gas: Option<U256>,
+    #[serde(rename="blobVersionedHashes")]
+    pub(crate) blob_versioned_hashes: Option<Vec<B256>>,
+    // Type 0x4 (set code) transactions only
+    #[serde(rename="authorizationList")]
+    pub(crate) authorization_list: Option<Vec<Authorization>>,
+    pub(crate) nonce: U256,
+    pub(crate) to: Option<Address>,
+    pub(crate) value: U256,
+    pub(crate) v: U256,
+    pub(crate) r: U256,
+    pub(crate) s: U256,
     #[serde(rename="secretKey")]
-    secret_key: String,
+    pub(crate) secret_key: Option<B256>,
     #[serde(rename="chainId")]
-    chain_id : String,
+    pub(crate) chain_id : U256,
     #[serde(rename="type")]
-    tx_type: Option<String>
+    pub(crate) tx_type: Option<U256>
 }
 
-impl TransactionT8n {
-  pub fn new(input: String, gas: String, gas_price: String, nonce: String, to: String, value: String, secret_key: String, tx_type: Option<String>) -> TransactionT8n {
-
-    let gas = hex_remove_leading_zero(gas);
-    let gas_price = hex_remove_leading_zero(gas_price);
-    let nonce = hex_remove_leading_zero(nonce);
-    let value = hex_remove_leading_zero(value);
+impl Default for TransactionT8n {
+    fn default() -> TransactionT8n {
+        TransactionT8n {
+            input      : Bytes::default(),
+            gas        : U256::from(0xaae60),
+            gas_price  : Some(U256::from(0xa)),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Some(Vec::new()),
+            max_fee_per_blob_gas: None,
+            blob_versioned_hashes: None,
+            authorization_list: None,
+            nonce      : U256::ZERO,
+            to         : None,
+            value      : U256::ZERO,
+            v          : U256::ZERO,
+            r          : U256::ZERO,
+            s          : U256::ZERO,
+            secret_key : None,
+            chain_id   : U256::from(1),
+            tx_type    : Some(U256::from(1))
+        }
+    }
+}
 
-    return TransactionT8n {
+impl TransactionT8n {
+  #[allow(clippy::too_many_arguments)]
+  pub fn new(input: Bytes, gas: U256, gas_price: U256, nonce: U256, to: Option<Address>, value: U256, secret_key: B256, tx_type: Option<U256>) -> TransactionT8n {
+    TransactionT8n {
       input,
       gas,
-      gas_price,
+      gas_price: Some(gas_price),
+      max_fee_per_gas: None,
+      max_priority_fee_per_gas: None,
+      access_list: None,
+      max_fee_per_blob_gas: None,
+      blob_versioned_hashes: None,
+      authorization_list: None,
       nonce,
-      to: None,
+      to,
       value,
-      v: String::from("0x0"),
-      r: String::from("0x0"),
-      s: String::from("0x0"),
-      secret_key: secret_key,
-      chain_id: String::from("0x1"),
+      v: U256::ZERO,
+      r: U256::ZERO,
+      s: U256::ZERO,
+      secret_key: Some(secret_key),
+      chain_id: U256::from(1),
       tx_type
     }
   }
-  pub fn default() -> TransactionT8n {
-      TransactionT8n {
-          input      : "".to_string(),
-          gas        : "0xaae60".to_string(),
-          gas_price  : "0xa".to_string(),
-          nonce      : "0x0".to_string(),
-          to         : None,
-          value      : "0x0".to_string(),
-          v          : "0x0".to_string(),
-          r          : "0x0".to_string(),
-          s          : "0x0".to_string(),
-          secret_key : "".to_string(),
-          chain_id   : "0x1".to_string(),
-          tx_type    : Some("0x1".to_string())
-      }
+
+  pub fn set_private_key(&mut self, pk: B256) {
+    self.secret_key = Some(pk);
   }
 
-  pub fn set_private_key(&mut self, pk: &str) {
-    self.secret_key = pk.to_string(); 
+  pub fn set_receiver(&mut self, address: Address) {
+    self.to = Some(address);
   }
 
-  pub fn set_receiver(&mut self, address: &str) {
-    self.to = Some(address.to_string());
+  pub fn set_input(&mut self, input_data: Bytes) {
+    self.input = input_data;
   }
 
-  pub fn set_input(&mut self, input_data: &str) {
-    self.input = input_data.to_string();
+  pub fn set_value(&mut self, value: U256) {
+    self.value = value;
   }
 
-  pub fn set_value(&mut self, value: &str) {
-    self.value = value.to_string();
+  pub fn set_gas(&mut self, gas: U256) {
+    self.gas = gas;
   }
 
-  pub fn set_gas(&mut self, gas: &str) {
-    self.gas = gas.to_string(); 
+  pub fn tx_type(&self) -> u64 {
+    self.tx_type.and_then(|t| t.to_u64()).unwrap_or(0)
+  }
+
+  // Switches between legacy (0x0), access list (0x1), dynamic fee (0x2), blob (0x3) and set code (0x4) transactions,
+  // moving the fee between gasPrice and maxFeePerGas so that t8n gets the fields it expects
+  pub fn set_tx_type(&mut self, tx_type: u64) -> Result<(), Error> {
+    let fee = self.gas_price.or(self.max_fee_per_gas).unwrap_or_default();
+    match tx_type {
+        0 | 1 => {
+            self.gas_price = Some(fee);
+            self.max_fee_per_gas = None;
+            self.max_priority_fee_per_gas = None;
+        },
+        2..=4 => {
+            self.gas_price = None;
+            self.max_fee_per_gas = Some(fee);
+            self.max_priority_fee_per_gas = Some(self.max_priority_fee_per_gas.unwrap_or_default());
+        },
+        _ => return Err(Error::invalid(format!("Unsupported transaction type {}", tx_type)))
+    }
+    self.access_list = match tx_type {
+        0 => None,
+        _ => Some(self.access_list.take().unwrap_or_default())
+    };
+    if tx_type == 3 {
+        self.max_fee_per_blob_gas = Some(self.max_fee_per_blob_gas.unwrap_or(U256::from(1)));
+        self.blob_versioned_hashes = Some(self.blob_versioned_hashes.take().unwrap_or_default());
+    } else {
+        self.max_fee_per_blob_gas = None;
+        self.blob_versioned_hashes = None;
+    }
+    self.authorization_list = match tx_type {
+        4 => Some(self.authorization_list.take().unwrap_or_default()),
+        _ => None
+    };
+    self.tx_type = Some(U256::from(tx_type));
+    Ok(())
+  }
+
+  pub fn set_gas_price(&mut self, gas_price: U256) {
+    self.gas_price = Some(gas_price);
+  }
+
+  pub fn set_max_fee_per_gas(&mut self, max_fee_per_gas: U256) {
+    self.max_fee_per_gas = Some(max_fee_per_gas);
+  }
+
+  pub fn set_max_priority_fee_per_gas(&mut self, max_priority_fee_per_gas: U256) {
+    self.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+  }
+
+  pub fn set_max_fee_per_blob_gas(&mut self, max_fee_per_blob_gas: U256) {
+    self.max_fee_per_blob_gas = Some(max_fee_per_blob_gas);
+  }
+
+  pub fn add_blob_versioned_hash(&mut self, hash: B256) {
+    self.blob_versioned_hashes.get_or_insert_with(Vec::new).push(hash);
+  }
+
+  pub fn add_authorization(&mut self, authorization: Authorization) {
+    self.authorization_list.get_or_insert_with(Vec::new).push(authorization);
+  }
+
+  // Adds `address` and `storage_keys` to the access list, merging with an existing entry
+  pub fn add_access_list_item(&mut self, address: Address, storage_keys: Vec<B256>) {
+    let access_list = self.access_list.get_or_insert_with(Vec::new);
+    match access_list.iter_mut().find(|item| item.address == address) {
+        Some(item) => item.storage_keys.extend(storage_keys.into_iter().filter(|k| !item.storage_keys.contains(k)).collect::<Vec<B256>>()),
+        None => access_list.push(AccessListItem { address, storage_keys })
+    }
+  }
+
+  pub fn has_secret_key(&self) -> bool {
+    self.secret_key.is_some()
+  }
+
+  // Address derived from the secret key, or recovered from v, r and s once the transaction
+  // is signed. None for transactions with neither
+  pub fn sender(&self) -> Result<Option<Address>, Error> {
+    if let Some(secret_key) = &self.secret_key {
+        return crypto::secret_key_address(secret_key).map(Some);
+    }
+    if self.r.is_zero() && self.s.is_zero() {
+        return Ok(None);
+    }
+    let invalid = || Error::invalid(format!("Invalid signature v {}", self.v));
+    let (eip155, recovery_id) = match self.tx_type() {
+        // v = recovery_id + 35 + 2 * chain_id, so an odd v has recovery id 0
+        0 if self.v >= U256::from(35) => (true, (self.v.to_be_bytes()[31] as u64 & 1) ^ 1),
+        0 => (false, self.v.to_u64().and_then(|v| v.checked_sub(27)).ok_or_else(invalid)?),
+        _ => (true, self.v.to_u64().ok_or_else(invalid)?)
+    };
+    crypto::recover_address(&self.signing_hash(eip155)?, recovery_id, &self.r, &self.s).map(Some)
+  }
+
+  // Hash signed by the sender: EIP-2718 typed payload, or for legacy transactions the
+  // EIP-155 payload with the chain id when `eip155` and the plain 6 field one otherwise
+  fn signing_hash(&self, eip155: bool) -> Result<B256, Error> {
+    let to = match &self.to {
+        Some(to) => rlp::encode_bytes(&to.0),
+        None => rlp::encode_bytes(&[])
+    };
+    let uint = |v: &U256| rlp::encode_uint(&v.to_be_bytes());
+    let missing = |field: &str| Error::invalid(format!("Transaction of type {} has no {}", self.tx_type(), field));
+    let access_list = rlp::encode_list(&self.access_list.iter().flatten().map(|item| rlp::encode_list(&[
+        rlp::encode_bytes(&item.address.0),
+        rlp::encode_list(&item.storage_keys.iter().map(|k| rlp::encode_bytes(&k.0)).collect::<Vec<Vec<u8>>>())
+    ])).collect::<Vec<Vec<u8>>>());
+    let (prefix, fields) = match self.tx_type() {
+        0 => {
+            let mut fields = vec![
+                uint(&self.nonce), uint(&self.gas_price.ok_or_else(|| missing("gasPrice"))?), uint(&self.gas), to, uint(&self.value),
+                rlp::encode_bytes(&self.input.0)
+            ];
+            if eip155 {
+                fields.extend([uint(&self.chain_id), uint(&U256::ZERO), uint(&U256::ZERO)]);
+            }
+            (None, fields)
+        },
+        1 => (Some(0x01), vec![
+            uint(&self.chain_id), uint(&self.nonce), uint(&self.gas_price.ok_or_else(|| missing("gasPrice"))?), uint(&self.gas), to,
+            uint(&self.value), rlp::encode_bytes(&self.input.0), access_list
+        ]),
+        2 => (Some(0x02), vec![
+            uint(&self.chain_id), uint(&self.nonce), uint(&self.max_priority_fee_per_gas.ok_or_else(|| missing("maxPriorityFeePerGas"))?),
+            uint(&self.max_fee_per_gas.ok_or_else(|| missing("maxFeePerGas"))?), uint(&self.gas), to, uint(&self.value),
+            rlp::encode_bytes(&self.input.0), access_list
+        ]),
+        3 => (Some(0x03), vec![
+            uint(&self.chain_id), uint(&self.nonce), uint(&self.max_priority_fee_per_gas.ok_or_else(|| missing("maxPriorityFeePerGas"))?),
+            uint(&self.max_fee_per_gas.ok_or_else(|| missing("maxFeePerGas"))?), uint(&self.gas),
+            rlp::encode_bytes(&self.to.ok_or_else(|| missing("receiver"))?.0), uint(&self.value),
+            rlp::encode_bytes(&self.input.0), access_list,
+            uint(&self.max_fee_per_blob_gas.ok_or_else(|| missing("maxFeePerBlobGas"))?),
+            rlp::encode_list(&self.blob_versioned_hashes.iter().flatten().map(|h| rlp::encode_bytes(&h.0)).collect::<Vec<Vec<u8>>>())
+        ]),
+        4 => (Some(0x04), vec![
+            uint(&self.chain_id), uint(&self.nonce), uint(&self.max_priority_fee_per_gas.ok_or_else(|| missing("maxPriorityFeePerGas"))?),
+            uint(&self.max_fee_per_gas.ok_or_else(|| missing("maxFeePerGas"))?), uint(&self.gas),
+            rlp::encode_bytes(&self.to.ok_or_else(|| missing("receiver"))?.0), uint(&self.value),
+            rlp::encode_bytes(&self.input.0), access_list,
+            rlp::encode_list(&self.authorization_list.iter().flatten().map(|a| a.rlp_encode()).collect::<Vec<Vec<u8>>>())
+        ]),
+        tx_type => return Err(Error::invalid(format!("Cannot sign transactions of type {}", tx_type)))
+    };
+    let mut payload: Vec<u8> = prefix.into_iter().collect();
+    payload.extend(rlp::encode_list(&fields));
+    Ok(crypto::keccak256(&payload))
+  }
+
+  // Signs the transaction with its secret key and fills v, r and s, legacy transactions
+  // are only replay protected (EIP-155) from `fork` on. The key is dropped afterwards since
+  // t8n would sign the transaction again if it is present, `sender` recovers it from v, r and s
+  pub fn sign(&mut self, fork: HardFork) -> Result<Address, Error> {
+    let secret_key = self.secret_key.ok_or_else(|| Error::invalid("Transaction has no secret key"))?;
+    let sender = crypto::secret_key_address(&secret_key)?;
+    let eip155 = fork.is_eip158();
+    let (recovery_id, r, s) = crypto::sign_hash(&secret_key, &self.signing_hash(eip155)?)?;
+    self.v = match self.tx_type() {
+        0 if eip155 => {
+            // v = recovery_id + 35 + 2 * chain_id
+            let v = U256::from(2).checked_mul(self.chain_id).and_then(|v| v.checked_add(U256::from(recovery_id + 35)));
+            v.ok_or_else(|| Error::invalid(format!("Chain id {} is too large for an EIP-155 signature", self.chain_id)))?
+        },
+        0 => U256::from(recovery_id + 27),
+        _ => U256::from(recovery_id)
+    };
+    self.r = r;
+    self.s = s;
+    self.secret_key = None;
+    Ok(sender)
   }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// Fields are optional since t8n omits empty ones in the post-state alloc
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Alloc {
-    balance: String,
-    code: String,
-    nonce: String,
-    storage: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) balance: U256,
+    #[serde(default)]
+    pub(crate) code: Bytes,
+    #[serde(default)]
+    pub(crate) nonce: U256,
+    #[serde(default)]
+    pub(crate) storage: HashMap<U256, U256>,
     #[serde(skip_serializing)]
-    secret_key: Option<String>
+    pub(crate) secret_key: Option<B256>
 }
 
 impl Alloc {
-    pub fn default() -> Alloc {
-        return Alloc {
-            balance: "0x0".to_string(),
-            code: "0x".to_string(),
-            nonce: "0x0".to_string(),
-            storage: HashMap::new(),
-            secret_key: None
-        }
+    pub fn set_code(&mut self, code: Bytes) -> Result<(), Error> {
+        self.code = code;
+        Ok(())
+    }
+
+    // Code listing with the opcodes of `fork`
+    pub fn disassemble(&self, fork: HardFork) -> String {
+        disasm::listing(&self.code.0, fork)
     }
-    pub fn set_code(&mut self, code: String) -> Result<(), &'static str> {
-        self.code = code; 
-        return Ok(())
+
+    // Validated EOF container of the code, None for legacy code
+    pub fn eof(&self) -> Option<Result<Container, EofError>> {
+        eof::is_eof(&self.code.0).then(|| eof::validate(&self.code.0))
+    }
+
+    // Control flow graph of the code with the opcodes of `fork`
+    pub fn cfg(&self, fork: HardFork) -> Cfg {
+        Cfg::build(&self.code.0, fork)
     }
-    
-    pub fn get_secret_key(&self) -> Option<String> {
-        return self.secret_key.clone();
+
+    pub fn get_secret_key(&self) -> Option<B256> {
+        self.secret_key
+    }
+
+    // Target of an EIP-7702 delegation designator (0xef0100 || address) set as the account code
+    pub fn delegation(&self) -> Option<Address> {
+        let target = self.code.0.strip_prefix(&[0xef, 0x01, 0x00])?;
+        Some(Address(target.try_into().ok()?))
     }
 }
 
+// keccak256(rlp([])), uncle hash of a block without ommers
+const EMPTY_UNCLE_HASH: B256 = B256([
+    0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a, 0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
+    0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13, 0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47
+]);
+
+// EIP-4895 beacon chain withdrawal, `amount` is in Gwei
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub index: U256,
+    #[serde(rename="validatorIndex")]
+    pub validator_index: U256,
+    pub address: Address,
+    pub amount: U256
+}
+
+// Uncle of the block, `delta` blocks older than it. Pre-Merge only
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ommer {
+    pub delta: u64,
+    pub address: Address
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Env {
+    // London and later
     #[serde(rename="currentBaseFee")]
-    current_base_fee : String,
+    pub(crate) current_base_fee : Option<U256>,
     #[serde(rename="currentCoinbase")]
-    current_coinbase : String,
+    pub(crate) current_coinbase : Address,
+    // Computed from the parent fields when not set
     #[serde(rename="currentDifficulty")]
-    current_difficulty : String,
+    pub(crate) current_difficulty : Option<U256>,
     #[serde(rename="currentGasLimit")]
-    current_gas_limit : String,
+    pub(crate) current_gas_limit : U256,
     #[serde(rename="currentNumber")]
-    current_number : String,
+    pub(crate) current_number : U256,
     #[serde(rename="currentTimestamp")]
-    current_timestamp : String,
+    pub(crate) current_timestamp : U256,
+    // Merge and later, replaces the difficulty
     #[serde(rename="currentRandom")]
-    current_random : Option<String>,
+    pub(crate) current_random : Option<B256>,
     #[serde(rename="previousHash")]
-    previous_hash : String
+    pub(crate) previous_hash : B256,
+    // Parent block values, used to compute currentBaseFee and currentDifficulty
+    #[serde(rename="parentBaseFee")]
+    pub(crate) parent_base_fee: Option<U256>,
+    #[serde(rename="parentGasUsed")]
+    pub(crate) parent_gas_used: Option<U256>,
+    #[serde(rename="parentGasLimit")]
+    pub(crate) parent_gas_limit: Option<U256>,
+    #[serde(rename="parentDifficulty")]
+    pub(crate) parent_difficulty: Option<U256>,
+    #[serde(rename="parentTimestamp")]
+    pub(crate) parent_timestamp: Option<U256>,
+    #[serde(rename="parentUncleHash")]
+    pub(crate) parent_uncle_hash: Option<B256>,
+    // Hashes returned by BLOCKHASH, by block number
+    #[serde(rename="blockHashes")]
+    pub(crate) block_hashes: Option<BTreeMap<U256, B256>>,
+    pub(crate) ommers: Option<Vec<Ommer>>,
+    // Cancun (EIP-4844, EIP-4788)
+    #[serde(rename="currentExcessBlobGas")]
+    pub(crate) current_excess_blob_gas: Option<U256>,
+    #[serde(rename="parentExcessBlobGas")]
+    pub(crate) parent_excess_blob_gas: Option<U256>,
+    #[serde(rename="parentBlobGasUsed")]
+    pub(crate) parent_blob_gas_used: Option<U256>,
+    #[serde(rename="parentBeaconBlockRoot")]
+    pub(crate) parent_beacon_block_root: Option<B256>,
+    // Shanghai (EIP-4895)
+    pub(crate) withdrawals: Option<Vec<Withdrawal>>
 }
 
-impl Env {
-    pub fn default() -> Env {
+impl Default for Env {
+    fn default() -> Env {
         Env {
-            current_base_fee : String::from("0x0a"),
-            current_coinbase : String::from("0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba"),
-            current_difficulty : String::from("0"),
-            current_gas_limit : String::from("0x05f5e100"),
-            current_number : String::from("0x01"),
-            current_timestamp : String::from("0x03e8"),
-            current_random : Some(String::from("0x0000000000000000000000000000000000000000000000000000000000020000")),
-            previous_hash : String::from("0x5e20a0453cecd065ea59c37ac63e079ee08998b6045136a8ce6635c7912ec0b6")
+            current_base_fee : Some(U256::from(0x0a)),
+            current_coinbase : "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba".parse().unwrap_or_default(),
+            current_difficulty : Some(U256::ZERO),
+            current_gas_limit : U256::from(0x05f5e100),
+            current_number : U256::from(0x01),
+            current_timestamp : U256::from(0x03e8),
+            current_random : Some(B256::from(U256::from(0x020000))),
+            previous_hash : "0x5e20a0453cecd065ea59c37ac63e079ee08998b6045136a8ce6635c7912ec0b6".parse().unwrap_or_default(),
+            parent_base_fee: None,
+            parent_gas_used: None,
+            parent_gas_limit: None,
+            parent_difficulty: None,
+            parent_timestamp: None,
+            parent_uncle_hash: None,
+            block_hashes: None,
+            ommers: None,
+            current_excess_blob_gas: None,
+            parent_excess_blob_gas: None,
+            parent_blob_gas_used: None,
+            parent_beacon_block_root: None,
+            withdrawals: None
         }
     }
+}
 
-    pub fn set_current_random(&mut self, cr: String) {
+impl Env {
+    pub fn set_current_random(&mut self, cr: B256) {
         self.current_random = Some(cr);
     }
 
-    pub fn set_current_difficulty(&mut self, diff: String) {
-        self.current_difficulty = diff;
+    pub fn set_current_difficulty(&mut self, diff: U256) {
+        self.current_difficulty = Some(diff);
+    }
+
+    // Sets the parent gas values, currentBaseFee is computed from them
+    pub fn set_parent_gas(&mut self, parent_base_fee: U256, parent_gas_used: U256, parent_gas_limit: U256) {
+        self.parent_base_fee = Some(parent_base_fee);
+        self.parent_gas_used = Some(parent_gas_used);
+        self.parent_gas_limit = Some(parent_gas_limit);
+        self.current_base_fee = None;
+    }
+
+    // Sets the parent difficulty values, currentDifficulty is computed from them
+    pub fn set_parent_difficulty(&mut self, parent_difficulty: U256, parent_timestamp: U256, parent_uncle_hash: Option<B256>) {
+        self.parent_difficulty = Some(parent_difficulty);
+        self.parent_timestamp = Some(parent_timestamp);
+        self.parent_uncle_hash = parent_uncle_hash;
+        self.current_difficulty = None;
+    }
+
+    pub fn set_block_hash(&mut self, number: U256, hash: B256) -> Result<(), Error> {
+        if number >= self.current_number {
+            return Err(Error::invalid(format!("Block {} is not older than the current block {}", number, self.current_number)));
+        }
+        self.block_hashes.get_or_insert_with(BTreeMap::new).insert(number, hash);
+        Ok(())
+    }
+
+    pub fn add_ommer(&mut self, delta: u64, address: Address) -> Result<&Ommer, Error> {
+        if delta == 0 || delta > 6 {
+            return Err(Error::invalid(format!("Ommer delta must be between 1 and 6, got {}", delta)));
+        }
+        let ommers = self.ommers.get_or_insert_with(Vec::new);
+        ommers.push(Ommer { delta, address });
+        Ok(&ommers[ommers.len() - 1])
+    }
+
+    pub fn remove_ommer(&mut self, ix: usize) -> Result<Ommer, Error> {
+        match &mut self.ommers {
+            Some(ommers) if ix < ommers.len() => Ok(ommers.remove(ix)),
+            _ => Err(Error::invalid(format!("Ommer {} not found", ix)))
+        }
+    }
+
+    // currentBaseFee, computed from the parent block (EIP-1559) when not set. The first
+    // London block gets the initial base fee
+    pub fn base_fee(&self, fork: HardFork) -> Result<Option<U256>, Error> {
+        if self.current_base_fee.is_some() {
+            return Ok(self.current_base_fee);
+        }
+        if !self.active_fork(fork).is_london() {
+            return Ok(None);
+        }
+        let number = match self.current_number.to_u64().and_then(|n| n.checked_sub(1)) {
+            Some(number) => number,
+            None => return Ok(None)
+        };
+        let parent_fork = fork.active(number, self.parent_timestamp.and_then(|t| t.to_u64()).unwrap_or(0), false);
+        if !parent_fork.is_london() {
+            return Ok(Some(U256::from(1_000_000_000)));
+        }
+        let (parent_base_fee, parent_gas_used, parent_gas_limit) = match (self.parent_base_fee, self.parent_gas_used, self.parent_gas_limit) {
+            (Some(fee), Some(used), Some(limit)) => (fee, used, limit),
+            _ => return Ok(None)
+        };
+        let gas_target = parent_gas_limit / U256::from(2);
+        let denominator = U256::from(8);
+        if gas_target.is_zero() || parent_gas_used == gas_target {
+            return Ok(Some(parent_base_fee));
+        }
+        let overflow = || Error::invalid(format!("currentBaseFee overflows with parentBaseFee {} and parentGasUsed {}", parent_base_fee, parent_gas_used));
+        if parent_gas_used > gas_target {
+            let delta = parent_base_fee.checked_mul(parent_gas_used - gas_target).ok_or_else(overflow)? / gas_target / denominator;
+            parent_base_fee.checked_add(delta.max(U256::from(1))).map(Some).ok_or_else(overflow)
+        } else {
+            let delta = parent_base_fee.checked_mul(gas_target - parent_gas_used).ok_or_else(overflow)? / gas_target / denominator;
+            Ok(Some(parent_base_fee.saturating_sub(delta)))
+        }
+    }
+
+    // currentDifficulty, computed from the parent block with the ethash rules of the fork
+    // when not set. Zero from the Merge on
+    pub fn difficulty(&self, fork: HardFork) -> Result<Option<U256>, Error> {
+        if self.current_difficulty.is_some() {
+            return Ok(self.current_difficulty);
+        }
+        let fork = self.active_fork(fork);
+        if fork.is_merge() {
+            return Ok(Some(U256::ZERO));
+        }
+        let elapsed = self.current_timestamp.to_u64().zip(self.parent_timestamp.and_then(|t| t.to_u64()))
+            .and_then(|(current, parent)| current.checked_sub(parent));
+        let (parent_difficulty, number, elapsed) = match (self.parent_difficulty, self.current_number.to_u64(), elapsed) {
+            (Some(difficulty), Some(number), Some(elapsed)) => (difficulty, number, elapsed),
+            _ => return Ok(None)
+        };
+        let has_uncles = self.parent_uncle_hash.is_some_and(|h| h != EMPTY_UNCLE_HASH);
+
+        // Adjustment factor, in units of parent_difficulty / 2048
+        let adjustment: i64 = if fork >= HardFork::Byzantium {
+            (if has_uncles { 2 } else { 1 } - (elapsed / 9) as i64).max(-99)
+        } else if fork >= HardFork::Homestead {
+            (1 - (elapsed / 10) as i64).max(-99)
+        } else if elapsed < 13 {
+            1
+        } else {
+            -1
+        };
+        let overflow = || Error::invalid(format!("currentDifficulty overflows with parentDifficulty {}", parent_difficulty));
+        let step = parent_difficulty / U256::from(2048);
+        let mut difficulty = if adjustment >= 0 {
+            step.checked_mul(U256::from(adjustment as u64)).and_then(|delta| parent_difficulty.checked_add(delta)).ok_or_else(overflow)?
+        } else {
+            parent_difficulty.saturating_sub(step * U256::from(adjustment.unsigned_abs()))
+        };
+        difficulty = difficulty.max(U256::from(131072));
+
+        // Difficulty bomb, delayed from Byzantium on
+        let bomb_delay: u64 = match fork {
+            f if f >= HardFork::GrayGlacier => 11_400_000,
+            f if f >= HardFork::ArrowGlacier => 10_700_000,
+            f if f >= HardFork::London => 9_700_000,
+            f if f >= HardFork::MuirGlacier => 9_000_000,
+            f if f >= HardFork::Constantinople => 5_000_000,
+            f if f >= HardFork::Byzantium => 3_000_000,
+            _ => 0
+        };
+        let period_count = number.saturating_sub(bomb_delay) / 100_000;
+        if period_count > 1 {
+            let exponent = period_count - 2;
+            if exponent >= 256 {
+                return Err(Error::invalid(format!("Difficulty bomb of block {} is 2^{}, it does not fit in 256 bits", number, exponent)));
+            }
+            difficulty = difficulty.checked_add(U256::pow2(exponent as usize)).ok_or_else(overflow)?;
+        }
+        Ok(Some(difficulty))
+    }
+
+    // Environment of the block following this one once `result` was produced: the current
+    // values become the parent ones and the derived fields are computed again. t8n does not
+    // report the block hash, so the previous hash becomes unknown (zero)
+    pub fn next_block(&self, result: &ExecutionResult, fork: HardFork) -> Result<Env, Error> {
+        let mut next = self.clone();
+        next.parent_base_fee = match result.current_base_fee {
+            Some(base_fee) => Some(base_fee),
+            None => self.base_fee(fork)?
+        };
+        next.parent_gas_used = Some(result.gas_used);
+        next.parent_gas_limit = Some(self.current_gas_limit);
+        next.parent_difficulty = match result.current_difficulty {
+            Some(difficulty) => Some(difficulty),
+            None => self.difficulty(fork)?
+        };
+        next.parent_timestamp = Some(self.current_timestamp);
+        next.parent_uncle_hash = None;
+        next.parent_excess_blob_gas = match result.current_excess_blob_gas {
+            Some(excess_blob_gas) => Some(excess_blob_gas),
+            None => self.excess_blob_gas(fork)?
+        };
+        next.parent_blob_gas_used = result.blob_gas_used;
+        next.current_base_fee = None;
+        next.current_difficulty = None;
+        next.current_excess_blob_gas = None;
+        if let Some(number) = self.current_number.to_u64().and_then(|n| n.checked_sub(1)).filter(|_| self.previous_hash != B256::default()) {
+            next.block_hashes.get_or_insert_with(BTreeMap::new).insert(U256::from(number), self.previous_hash);
+        }
+        next.previous_hash = B256::default();
+        next.ommers = None;
+        next.current_number = self.current_number.checked_add(U256::from(1))
+            .ok_or_else(|| Error::invalid(format!("currentNumber {} overflows", self.current_number)))?;
+        next.current_timestamp = self.current_timestamp.checked_add(U256::from(12))
+            .ok_or_else(|| Error::invalid(format!("currentTimestamp {} overflows", self.current_timestamp)))?;
+        if let Some(withdrawals) = &mut next.withdrawals {
+            withdrawals.clear();
+        }
+        let active = next.active_fork(fork);
+        if !active.is_london() {
+            next.parent_base_fee = None;
+        }
+        if !active.is_cancun() {
+            next.parent_excess_blob_gas = None;
+            next.parent_blob_gas_used = None;
+        }
+        Ok(next)
+    }
+
+    pub fn set_current_excess_blob_gas(&mut self, excess_blob_gas: U256) {
+        self.current_excess_blob_gas = Some(excess_blob_gas);
+    }
+
+    pub fn set_parent_blob_gas(&mut self, parent_excess_blob_gas: U256, parent_blob_gas_used: U256) {
+        self.parent_excess_blob_gas = Some(parent_excess_blob_gas);
+        self.parent_blob_gas_used = Some(parent_blob_gas_used);
+    }
+
+    pub fn set_parent_beacon_block_root(&mut self, root: B256) {
+        self.parent_beacon_block_root = Some(root);
+    }
+
+    // Appends a withdrawal, its index follows the last one in the list
+    pub fn add_withdrawal(&mut self, validator_index: U256, address: Address, amount: U256) -> &Withdrawal {
+        let withdrawals = self.withdrawals.get_or_insert_with(Vec::new);
+        let index = withdrawals.last()
+            .and_then(|w| w.index.to_u64())
+            .map(|i| U256::from(i + 1))
+            .unwrap_or_default();
+        withdrawals.push(Withdrawal { index, validator_index, address, amount });
+        &withdrawals[withdrawals.len() - 1]
+    }
+
+    // Removes the withdrawal at position `ix` of the list
+    pub fn remove_withdrawal(&mut self, ix: usize) -> Result<Withdrawal, Error> {
+        match &mut self.withdrawals {
+            Some(withdrawals) if ix < withdrawals.len() => Ok(withdrawals.remove(ix)),
+            _ => Err(Error::invalid(format!("Withdrawal {} not found", ix)))
+        }
+    }
+
+    // currentExcessBlobGas, computed from the parent block values (EIP-4844) when not set.
+    // Prague doubled the blob target (EIP-7691)
+    pub fn excess_blob_gas(&self, fork: HardFork) -> Result<Option<U256>, Error> {
+        if self.current_excess_blob_gas.is_some() {
+            return Ok(self.current_excess_blob_gas);
+        }
+        let target_blob_gas: u64 = if self.active_fork(fork).is_prague() { 786432 } else { 393216 };
+        let (parent_excess, parent_used) = match (self.parent_excess_blob_gas, self.parent_blob_gas_used) {
+            (Some(excess), Some(used)) => (excess, used),
+            _ => return Ok(None)
+        };
+        let parent = parent_excess.to_u64().zip(parent_used.to_u64()).and_then(|(excess, used)| excess.checked_add(used))
+            .ok_or_else(|| Error::invalid(format!("parentExcessBlobGas {} and parentBlobGasUsed {} overflow", parent_excess, parent_used)))?;
+        Ok(Some(U256::from(parent.saturating_sub(target_blob_gas))))
+    }
+
+    // Rules in effect for this block when `fork` is a transition fork
+    pub fn active_fork(&self, fork: HardFork) -> HardFork {
+        let number = self.current_number.to_u64().unwrap_or(u64::MAX);
+        let timestamp = self.current_timestamp.to_u64().unwrap_or(u64::MAX);
+        fork.active(number, timestamp, self.current_difficulty.is_some_and(|d| d.is_zero()))
+    }
+
+    pub fn for_fork(fork: HardFork) -> Env {
+        let mut env = Env::default();
+        env.apply_fork_defaults(fork);
+        env
+    }
+
+    // Adds the fields introduced up to `fork` with their default values and drops the
+    // ones introduced later
+    pub fn apply_fork_defaults(&mut self, fork: HardFork) {
+        let fork = self.active_fork(fork);
+        if fork.is_london() {
+            self.current_base_fee.get_or_insert(U256::from(0x0a));
+        } else {
+            self.current_base_fee = None;
+        }
+        if fork.is_merge() {
+            self.current_random.get_or_insert(B256::from(U256::from(0x020000)));
+            self.current_difficulty = Some(U256::ZERO);
+            self.ommers = None;
+        } else {
+            self.current_random = None;
+            if self.current_difficulty.map_or(self.parent_difficulty.is_none(), |d| d.is_zero()) {
+                self.current_difficulty = match self.parent_difficulty {
+                    Some(_) => None,
+                    None => Some(U256::from(0x020000))
+                };
+            }
+        }
+        if fork.is_shanghai() {
+            self.withdrawals.get_or_insert_with(Vec::new);
+        } else {
+            self.withdrawals = None;
+        }
+        if fork.is_cancun() {
+            if matches!(self.excess_blob_gas(fork), Ok(None)) {
+                self.current_excess_blob_gas = Some(U256::ZERO);
+            }
+            self.parent_beacon_block_root.get_or_insert_with(B256::default);
+        } else {
+            self.current_excess_blob_gas = None;
+            self.parent_excess_blob_gas = None;
+            self.parent_blob_gas_used = None;
+            self.parent_beacon_block_root = None;
+        }
+    }
+
+    // Environment handed to t8n for `fork`: fields with a neutral value (no withdrawals,
+    // zero beacon root, excess blob gas from the parent) are filled in, missing required
+    // fields and fields the fork does not know about are errors
+    pub fn prepared(&self, fork: HardFork) -> Result<Env, Error> {
+        let active = self.active_fork(fork);
+        let mut env = self.clone();
+        if active.is_london() {
+            env.current_base_fee = Some(env.base_fee(fork)?
+                .ok_or_else(|| Error::invalid(format!("{} requires currentBaseFee or parentBaseFee, parentGasUsed and parentGasLimit", active)))?);
+        }
+        env.current_difficulty = Some(env.difficulty(fork)?
+            .ok_or_else(|| Error::invalid(format!("{} requires currentDifficulty or parentDifficulty and parentTimestamp", active)))?);
+        if active.is_merge() && env.current_random.is_none() {
+            return Err(Error::invalid(format!("{} requires currentRandom", active)));
+        }
+        if active.is_merge() && env.ommers.as_ref().is_some_and(|o| !o.is_empty()) {
+            return Err(Error::invalid(format!("{} does not support ommers", active)));
+        }
+        // BLOCKHASH of the parent block resolves to previousHash, unless it is unknown
+        if let Some(number) = env.current_number.to_u64().and_then(|n| n.checked_sub(1)).filter(|_| env.previous_hash != B256::default()) {
+            env.block_hashes.get_or_insert_with(BTreeMap::new).entry(U256::from(number)).or_insert(env.previous_hash);
+        }
+        if !active.is_shanghai() && env.withdrawals.is_some() {
+            return Err(Error::invalid(format!("{} does not support withdrawals", active)));
+        }
+        if active.is_shanghai() {
+            env.withdrawals.get_or_insert_with(Vec::new);
+        }
+        if active.is_cancun() {
+            env.current_excess_blob_gas = Some(env.excess_blob_gas(fork)?.unwrap_or_default());
+            env.parent_beacon_block_root.get_or_insert_with(B256::default);
+        }
+        Ok(env)
     }
 }
 
-#[derive(Deserialize)]
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Directory of a single t8n run, removed with everything in it when dropped
+struct RunDir {
+    path: String
+}
+
+impl RunDir {
+    fn create(work_dir: &str) -> Result<RunDir, Error> {
+        let run = RUN_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = format!("{}/run-{}-{}", work_dir, std::process::id(), run);
+        fs::create_dir_all(&path).map_err(|e| Error::io(&path, e))?;
+        Ok(RunDir { path })
+    }
+}
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// EIP-3155 quantities are hex strings, but some tools emit plain numbers
+fn deserialize_quantity<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where D: serde::Deserializer<'de> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    match value {
+        serde_json::Value::Number(n) => n.as_u64().ok_or_else(|| serde::de::Error::custom("invalid quantity")),
+        serde_json::Value::String(s) => {
+            let parsed = match s.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => s.parse::<u64>()
+            };
+            parsed.map_err(|_| serde::de::Error::custom(format!("invalid quantity {}", s)))
+        },
+        _ => Err(serde::de::Error::custom("invalid quantity"))
+    }
+}
+
+// Single EIP-3155 trace line, emitted before each executed opcode
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TraceStep {
+    pub pc: u64,
+    pub op: u8,
+    #[serde(deserialize_with="deserialize_quantity")]
+    pub gas: u64,
+    #[serde(rename="gasCost", deserialize_with="deserialize_quantity")]
+    pub gas_cost: u64,
+    #[serde(rename="memSize", default)]
+    pub mem_size: u64,
+    #[serde(default)]
+    pub stack: Vec<U256>,
+    #[serde(rename="returnData")]
+    pub return_data: Option<Bytes>,
+    pub depth: u64,
+    #[serde(default, deserialize_with="deserialize_quantity")]
+    pub refund: u64,
+    #[serde(rename="opName")]
+    pub op_name: Option<String>,
+    pub error: Option<String>
+}
+
+// Final EIP-3155 line, emitted once the transaction has been executed
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TraceSummary {
+    #[serde(rename="stateRoot")]
+    pub state_root: Option<B256>,
+    #[serde(default)]
+    pub output: Bytes,
+    #[serde(rename="gasUsed", deserialize_with="deserialize_quantity")]
+    pub gas_used: u64,
+    pub pass: Option<bool>,
+    pub time: Option<u64>,
+    pub fork: Option<String>,
+    pub error: Option<String>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Trace {
+    pub tx_index: usize,
+    pub tx_hash: String,
+    pub steps: Vec<TraceStep>,
+    pub summary: Option<TraceSummary>
+}
 
+impl Trace {
+    // t8n writes one `trace-<txIndex>-<txHash>.jsonl` file per transaction
+    pub fn from_file(path: &Path) -> Result<Trace, Error> {
+        let path_str = path.to_string_lossy();
+        let invalid_name = || Error::invalid(format!("Invalid trace file name {}", path_str));
+        let fname = path.file_stem().and_then(|f| f.to_str()).ok_or_else(invalid_name)?;
+        let mut name_parts = fname.splitn(3, '-').skip(1);
+        let tx_index = name_parts.next()
+            .and_then(|ix| ix.parse::<usize>().ok())
+            .ok_or_else(invalid_name)?;
+        let tx_hash = name_parts.next().unwrap_or("").to_string();
+        Trace::from_jsonl(path, tx_index, tx_hash)
+    }
+
+    // EIP-3155 trace of any tool, one JSON object per line
+    pub fn from_jsonl(path: &Path, tx_index: usize, tx_hash: String) -> Result<Trace, Error> {
+        let path_str = path.to_string_lossy();
+        let contents = fs::read_to_string(path).map_err(|e| Error::io(&path_str, e))?;
+        let mut steps: Vec<TraceStep> = Vec::new();
+        let mut summary: Option<TraceSummary> = None;
+
+        for (line_ix, line) in contents.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            // Report the position within the trace file rather than within the line
+            let line_error = |e: serde_json::Error| Error::Json { path: path_str.to_string(), line: line_ix + 1, column: e.column(), message: e.to_string() };
+            let value: serde_json::Value = serde_json::from_str(line).map_err(line_error)?;
+            if value.get("pc").is_some() {
+                steps.push(serde_json::from_value(value).map_err(line_error)?);
+            } else {
+                summary = Some(serde_json::from_value(value).map_err(line_error)?);
+            }
+        }
+
+        Ok(Trace { tx_index, tx_hash, steps, summary })
+    }
+
+    pub fn print(&self) {
+        println!("Trace tx {} ({})", self.tx_index, self.tx_hash);
+        for step in &self.steps {
+            let op_name = step.op_name.clone().unwrap_or(format!("0x{:02x}", step.op));
+            print!("{:>6} {:<14} gas: {:<10} cost: {:<8} depth: {} stack: [{}]", step.pc, op_name, step.gas, step.gas_cost, step.depth, step.stack.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", "));
+            match &step.error {
+                Some(err) => println!(" error: {}", err),
+                None => println!()
+            }
+        }
+        if let Some(summary) = &self.summary {
+            print!("output: {} gasUsed: {}", summary.output, summary.gas_used);
+            match &summary.error {
+                Some(err) => println!(" error: {}", err),
+                None => println!()
+            }
+        }
+    }
+}
+
+// Everything produced by a single t8n execution
+#[derive(Debug, Clone)]
+pub struct RunOutput {
+    pub result: ExecutionResult,
+    pub alloc: HashMap<Address, Alloc>,
+    pub traces: Vec<Trace>
+}
+
+impl RunOutput {
+    // Accounts whose post-state code is a delegation designator
+    pub fn print_delegations(&self) {
+        let mut delegations: Vec<(&Address, Address)> = self.alloc.iter()
+            .filter_map(|(address, alloc)| alloc.delegation().map(|target| (address, target)))
+            .collect();
+        delegations.sort();
+        for (address, target) in delegations {
+            println!("{} delegated to {}", address, target);
+        }
+    }
+}
+
+// t8n result file (--output.result)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExecutionResult {
+    #[serde(rename="stateRoot")]
+    pub state_root: B256,
+    #[serde(rename="txRoot")]
+    pub tx_root: B256,
+    #[serde(rename="receiptsRoot")]
+    pub receipts_root: B256,
+    #[serde(rename="logsHash")]
+    pub logs_hash: B256,
+    #[serde(rename="logsBloom")]
+    pub logs_bloom: Bytes,
+    #[serde(default)]
+    pub receipts: Vec<Receipt>,
+    #[serde(default)]
+    pub rejected: Vec<RejectedTx>,
+    #[serde(rename="currentDifficulty")]
+    pub current_difficulty: Option<U256>,
+    #[serde(rename="gasUsed")]
+    pub gas_used: U256,
+    #[serde(rename="currentBaseFee")]
+    pub current_base_fee: Option<U256>,
+    #[serde(rename="currentExcessBlobGas")]
+    pub current_excess_blob_gas: Option<U256>,
+    #[serde(rename="blobGasUsed")]
+    pub blob_gas_used: Option<U256>,
+    #[serde(rename="withdrawalsRoot")]
+    pub withdrawals_root: Option<B256>
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Receipt {
+    #[serde(rename="type")]
+    pub tx_type: Option<U256>,
+    pub root: Option<Bytes>,
+    pub status: U256,
+    #[serde(rename="cumulativeGasUsed")]
+    pub cumulative_gas_used: U256,
+    #[serde(rename="logsBloom")]
+    pub logs_bloom: Bytes,
+    pub logs: Option<Vec<Log>>,
+    #[serde(rename="transactionHash")]
+    pub transaction_hash: B256,
+    #[serde(rename="contractAddress")]
+    pub contract_address: Option<Address>,
+    #[serde(rename="gasUsed")]
+    pub gas_used: U256,
+    #[serde(rename="effectiveGasPrice")]
+    pub effective_gas_price: Option<U256>,
+    #[serde(rename="blockHash")]
+    pub block_hash: Option<B256>,
+    #[serde(rename="transactionIndex")]
+    pub transaction_index: U256
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    pub data: Bytes
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RejectedTx {
+    pub index: u64,
+    pub error: String
+}
+
+impl ExecutionResult {
+    pub fn from_file(fname: &str) -> Result<ExecutionResult, Error> {
+        read_json(fname)
+    }
+
+    pub fn is_rejected(&self, tx_index: u64) -> bool {
+        self.rejected.iter().any(|r| r.index == tx_index)
+    }
+
+    pub fn print(&self) {
+        println!("stateRoot:    {}", self.state_root);
+        println!("txRoot:       {}", self.tx_root);
+        println!("receiptsRoot: {}", self.receipts_root);
+        println!("logsHash:     {}", self.logs_hash);
+        println!("gasUsed:      {}", self.gas_used);
+        if let Some(withdrawals_root) = &self.withdrawals_root {
+            println!("withdrawalsRoot: {}", withdrawals_root);
+        }
+        if let Some(blob_gas_used) = &self.blob_gas_used {
+            println!("blobGasUsed:  {}", blob_gas_used);
+        }
+        if let Some(excess_blob_gas) = &self.current_excess_blob_gas {
+            println!("excessBlobGas: {}", excess_blob_gas);
+        }
+        for receipt in &self.receipts {
+            println!("tx {}: status {} gasUsed {} hash {}", receipt.transaction_index, receipt.status, receipt.gas_used, receipt.transaction_hash);
+        }
+        for rejected in &self.rejected {
+            println!("tx {}: rejected ({})", rejected.index, rejected.error);
+        }
+    }
+}
+
+// Expected post state of a state test case
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PostExpectation {
+    pub test_name: String,
+    pub post: HashMap<String, Vec<Post>>,
+    pub indexes: Indexes
+}
+
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String
+}
+
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub test_name: String,
+    pub fork: String,
+    pub indexes: Indexes,
+    pub mismatches: Vec<Mismatch>
+}
+
+impl Verification {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    pub fn print(&self) {
+        let case = format!("{} {} d{} g{} v{}", self.test_name, self.fork, self.indexes.data, self.indexes.gas, self.indexes.value);
+        if self.passed() {
+            println!("PASS {}", case);
+        } else {
+            println!("FAIL {}", case);
+            for m in &self.mismatches {
+                println!("\t{}: expected {}, got {}", m.field, m.expected, m.actual);
+            }
+        }
+    }
+}
+
+// Post entries of `fork`, fixtures may name it with an alias (Paris for Merge)
+fn fork_posts(post: &HashMap<String, Vec<Post>>, fork: HardFork) -> Option<&Vec<Post>> {
+    post.iter()
+        .find(|(name, _)| name.parse::<HardFork>().ok() == Some(fork))
+        .map(|(_, posts)| posts)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Context {
     pub config : Config,
-    pub alloc : HashMap<String, Alloc>,
+    pub alloc : HashMap<Address, Alloc>,
     pub env : Env,
-    pub txs: Vec<TransactionT8n>
+    pub txs: Vec<TransactionT8n>,
+    #[serde(default)]
+    pub expect: Option<PostExpectation>,
+    // Mining reward in wei passed to t8n, -1 disables it
+    #[serde(default)]
+    pub reward: Option<i64>,
+    // Result of the last run, used to build the next block
+    #[serde(skip)]
+    pub last_result: Option<ExecutionResult>,
+    // Traces of the last run, used to highlight executed code
+    #[serde(skip)]
+    pub last_traces: Vec<Trace>
+}
+
+// Environment defaults follow the configured fork
+impl Default for Context {
+    fn default() -> Context {
+        Context::with_config(Config::default())
+    }
 }
 
 impl Context {
-    pub fn default() -> Context {
-        Context {
-            config: Config::default(),
-            alloc: HashMap::new(),
-            env: Env::default(),
-            txs: Vec::new()
-        }
+    // Context with an already loaded config, `default` reads the config file again
+    pub(crate) fn with_config(config: Config) -> Context {
+        let env = Env::for_fork(config.hard_fork);
+        Context { config, alloc: HashMap::new(), env, txs: Vec::new(), expect: None, reward: None, last_result: None, last_traces: Vec::new() }
     }
 
-    pub fn add_address(&mut self, address: &str) -> Result<(), &'static str> {
-        self.alloc.insert(address.to_string(),
+    pub fn add_address(&mut self, address: Address) -> Result<(), Error> {
+        self.alloc.insert(address,
                           Alloc::default());
 
         Ok(())
     }
 
-    pub fn add_default_address(&mut self) -> Result<(), &'static str> {
-        let default_alloc = Alloc {
-            balance: "0x3b9aca00".to_string(),
-            code: "".to_string(),
-            nonce: "0x0".to_string(),
-            storage: HashMap::new(),
-            secret_key: Some("0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8".to_string())
+    // Adds a funded account controlled by `secret_key`, returns its address
+    pub fn add_secret_key(&mut self, secret_key: B256) -> Result<Address, Error> {
+        let address = crypto::secret_key_address(&secret_key)?;
+        let alloc = Alloc {
+            balance: U256::from(0x3b9aca00),
+            secret_key: Some(secret_key),
+            ..Default::default()
         };
+        self.alloc.insert(address, alloc);
+        Ok(address)
+    }
 
-        self.alloc.insert("0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b".to_string(), 
-            default_alloc);
-        Ok(())
+    pub fn add_default_address(&mut self) -> Result<Address, Error> {
+        self.add_secret_key("0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8".parse()?)
     }
 
-    pub fn address_exists(&self, address: &str) -> bool {
-        self.alloc.get(address).is_some()
+    // Signs an authorization of `authority` (an account of the alloc holding its secret key)
+    // delegating to `delegate`. Without an explicit nonce the account nonce is used, plus
+    // one when the authority also sends transaction `tx_ix` since its nonce is bumped first
+    pub fn authorize(&self, tx_ix: usize, authority: &Address, delegate: Address, nonce: Option<U256>) -> Result<Authorization, Error> {
+        let account = self.alloc.get(authority).ok_or_else(|| Error::invalid(format!("Address {} not found", authority)))?;
+        let secret_key = account.secret_key.ok_or_else(|| Error::invalid(format!("Address {} has no secret key", authority)))?;
+        let tx = self.txs.get(tx_ix).ok_or_else(|| Error::invalid(format!("Transaction {} not found", tx_ix)))?;
+        let nonce = match nonce {
+            Some(nonce) => nonce,
+            None if tx.sender()? == Some(*authority) => account.nonce.to_u64().and_then(|n| n.checked_add(1)).map(U256::from)
+                .ok_or_else(|| Error::invalid(format!("Nonce {} of {} is too large for an authorization", account.nonce, authority)))?,
+            None => account.nonce
+        };
+        Authorization::sign(tx.chain_id, delegate, nonce, &secret_key)
     }
 
-    pub fn get_secret_key(&mut self, address: String) -> Option<String> {
-        let alloc = self.alloc.get(address.as_str());
+    pub fn address_exists(&self, address: &Address) -> bool {
+        self.alloc.contains_key(address)
+    }
 
-        if alloc.is_some() {
-            return alloc.unwrap().get_secret_key()
-        } else {
-            return None
-        }
-    }
-
-    pub fn from_state_test(st_name: &str) -> Result<Context, &'static str> {
-        let mut ctx = Context::default();
-        let json_file = fs::File::open(st_name);
-
-        if json_file.is_ok() {
-            let json: HashMap<String, StateTestContent>  = serde_json::from_reader(BufReader::new(json_file.unwrap())).unwrap();
-            let json_clone = json.clone();
-            let test_name: String = json_clone.into_keys().collect(); 
-            let state_test = json[test_name.as_str()].clone();
-            let transaction = state_test.transaction.clone();
-            ctx.alloc = state_test.pre.clone();
-            ctx.env = state_test.env.clone();
-
-            let mut txs : Vec<TransactionT8n> = Vec::new();
-
-            for (i, d) in transaction.data.iter().enumerate() {
-                let tx_st = transaction.clone();
-                let nonce = tx_st.nonce.clone();
-                let nonce = &nonce[2..];
-                let mut nonce = i64::from_str_radix(nonce, 16).unwrap();
-                nonce = nonce + (i as i64);
-                let nonce = format!("0x{:x}", nonce);
-                let nonce = if nonce.as_str() == "0x" {
-                    String::from("0x0")
-                } else {
-                    nonce
-                };
-                let gas_limit = if transaction.gas_limit.len() <= i {
-                    transaction.gas_limit.last().unwrap().clone()
-                  } else {
-                    transaction.gas_limit[i].clone() 
-                  };
-                let tx_value = if transaction.value.len() <= i {
-                    transaction.value.last().unwrap().clone()
-                } else {
-                    transaction.value[i].clone()
-                };
+    pub fn get_secret_key(&self, address: &Address) -> Option<B256> {
+        self.alloc.get(address).and_then(|alloc| alloc.get_secret_key())
+    }
 
-                let tx_ctx = TransactionT8n::new(
-                  d.to_string(), 
-                  gas_limit,
-                  tx_st.gas_price, 
-                  nonce,
-                  tx_st.to, 
-                  tx_value,
-                  tx_st.secret_key, 
-                  None);
-
-                txs.push(tx_ctx);
-            }
-            ctx.txs = txs;
-            return Ok(ctx);
-        } else {
-            return Err("Error opening state test");
-        }
+    fn load_state_tests(st_name: &str) -> Result<HashMap<String, StateTestContent>, Error> {
+        read_json(st_name)
     }
 
-    pub fn set_work_dir(&mut self, wd: &str) -> bool {
-        if ! Path::new(wd).exists() {
-            return false;
-        }
-        self.config.work_dir = wd.to_string();
-        return true;
+    // Names of the tests contained in a state test file, sorted
+    pub fn list_state_tests(st_name: &str) -> Result<Vec<String>, Error> {
+        let tests = Context::load_state_tests(st_name)?;
+        let mut names: Vec<String> = tests.into_keys().collect();
+        names.sort();
+        Ok(names)
     }
 
-    pub fn save(&self, fname: &str) -> Result<(), ()> {
-        let file_result = fs::File::create(fname); 
+    fn select_state_tests(st_name: &str, test_name: Option<&str>) -> Result<Vec<(String, StateTestContent)>, Error> {
+        let tests = Context::load_state_tests(st_name)?;
+        let mut selected: Vec<(String, StateTestContent)> = match test_name {
+            Some(name) => {
+                let state_test = tests.get(name).ok_or_else(|| Error::fixture(format!("test `{}` not found in {}", name, st_name)))?;
+                vec![(name.to_string(), state_test.clone())]
+            },
+            None => tests.into_iter().collect()
+        };
+        selected.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(selected)
+    }
 
-        if file_result.is_err() {
-            return Err(())
+    // Builds the context of a single (data, gas, value) case. Without a test name the
+    // file must contain exactly one test.
+    pub fn from_state_test(st_name: &str, test_name: Option<&str>, indexes: &Indexes) -> Result<Context, Error> {
+        let tests = Context::select_state_tests(st_name, test_name)?;
+        if tests.len() != 1 {
+            return Err(Error::invalid(format!("{} contains {} tests, a test name is required", st_name, tests.len())));
         }
+        let (name, state_test) = &tests[0];
+        Context::from_state_test_case(name, state_test, indexes, &Config::default())
+    }
 
-        let mut file = file_result.unwrap();
-        let ctx_str = serde_json::to_string(&self).unwrap();
-        if file.write(ctx_str.as_bytes()).is_ok() {
-            return Ok(())
-        } else {
-            return Err(())
+    // Builds one context per case referenced by the `post` section of `fork`, for the
+    // named test or for every test in the file
+    pub fn state_test_cases(st_name: &str, test_name: Option<&str>, fork: HardFork) -> Result<Vec<Context>, Error> {
+        let mut contexts: Vec<Context> = Vec::new();
+        let config = Config::default();
+        for (name, state_test) in Context::select_state_tests(st_name, test_name)? {
+            if let Some(posts) = fork_posts(&state_test.post, fork) {
+                for post in posts {
+                    contexts.push(Context::from_state_test_case(&name, &state_test, &post.indexes, &config)?);
+                }
+            }
         }
+        Ok(contexts)
     }
 
-    pub fn load(&mut self, fname: &str) -> Result<(), ()> {
-        let file = fs::File::open(fname);
+    // Builds one context per case of every test of the file, for `fork` or, without it, for
+    // every fork the post sections know about. A case that cannot be built does not stop the
+    // others. Also returns how many cases were skipped: posts for forks that are not
+    // supported and, for a test without posts for `fork`, every (data, gas, value) case of
+    // its transaction
+    pub fn state_test_batch_cases(st_name: &str, test_name: Option<&str>, fork: Option<HardFork>) -> Result<(Vec<Result<Context, Error>>, usize), Error> {
+        let mut contexts: Vec<Result<Context, Error>> = Vec::new();
+        let mut skipped = 0;
+        let config = Config::default();
+        for (name, state_test) in Context::select_state_tests(st_name, test_name)? {
+            let mut posts: Vec<(HardFork, &Vec<Post>)> = Vec::new();
+            for (fork_name, fork_posts) in &state_test.post {
+                match fork_name.parse::<HardFork>() {
+                    Ok(post_fork) if fork.is_none() || fork == Some(post_fork) => posts.push((post_fork, fork_posts)),
+                    Ok(_) => {},
+                    Err(_) if fork.is_none() => skipped += fork_posts.len(),
+                    Err(_) => {}
+                }
+            }
+            if posts.is_empty() && fork.is_some() {
+                let transaction = &state_test.transaction;
+                skipped += transaction.data.len() * transaction.gas_limit.len() * transaction.value.len();
+            }
+            posts.sort_by_key(|(post_fork, _)| *post_fork);
+            for (post_fork, fork_posts) in posts {
+                for post in fork_posts {
+                    contexts.push(Context::from_state_test_case(&name, &state_test, &post.indexes, &config).map(|mut ctx| {
+                        ctx.config.hard_fork = post_fork;
+                        ctx
+                    }));
+                }
+            }
+        }
+        Ok((contexts, skipped))
+    }
 
-        if file.is_err() {
-            return Err(())
+    // Indexes referenced by the `post` section of `fork`
+    pub fn state_test_indexes(st_name: &str, test_name: Option<&str>, fork: HardFork) -> Result<Vec<Indexes>, Error> {
+        let tests = Context::select_state_tests(st_name, test_name)?;
+        if tests.len() != 1 {
+            return Err(Error::invalid(format!("{} contains {} tests, a test name is required", st_name, tests.len())));
         }
+        let posts = fork_posts(&tests[0].1.post, fork).cloned().unwrap_or_default();
+        Ok(posts.into_iter().map(|p| p.indexes).collect())
+    }
 
-        let mut context: Result<Context, serde_json::Error> = serde_json::from_reader(BufReader::new(file.unwrap()));
+    // Every case is executed against its own pre-state, so the nonce is taken as is. The
+    // config is loaded once by the caller and copied into every case
+    fn from_state_test_case(test_name: &str, state_test: &StateTestContent, indexes: &Indexes, config: &Config) -> Result<Context, Error> {
+        let mut ctx = Context::with_config(config.clone());
+        let transaction = &state_test.transaction;
+        ctx.alloc = state_test.pre.clone();
+        ctx.env = state_test.env.clone();
+
+        let out_of_range = |field: &str, ix: u64| Error::fixture(format!("{}: {} index {} out of range", test_name, field, ix));
+        let data = transaction.data.get(indexes.data as usize).ok_or_else(|| out_of_range("data", indexes.data))?;
+        let gas_limit = transaction.gas_limit.get(indexes.gas as usize).ok_or_else(|| out_of_range("gas", indexes.gas))?;
+        let value = transaction.value.get(indexes.value as usize).ok_or_else(|| out_of_range("value", indexes.value))?;
+
+        let mut tx = TransactionT8n::new(
+            data.clone(),
+            *gas_limit,
+            transaction.gas_price.unwrap_or_default(),
+            transaction.nonce,
+            transaction.to,
+            *value,
+            transaction.secret_key,
+            None);
+
+        // The transaction type follows from the fields present: authorizations for 0x4, blob
+        // hashes for 0x3, maxFeePerGas for 0x2, an access list for this data index for 0x1
+        let access_list = transaction.access_lists.as_ref()
+            .and_then(|lists| lists.get(indexes.data as usize).cloned().flatten());
+        if let Some(max_fee_per_gas) = transaction.max_fee_per_gas {
+            if transaction.authorization_list.is_some() {
+                tx.set_tx_type(4)?;
+                tx.authorization_list = transaction.authorization_list.clone();
+            } else if transaction.blob_versioned_hashes.is_some() {
+                tx.set_tx_type(3)?;
+                tx.max_fee_per_blob_gas = transaction.max_fee_per_blob_gas;
+                tx.blob_versioned_hashes = transaction.blob_versioned_hashes.clone();
+            } else {
+                tx.set_tx_type(2)?;
+            }
+            tx.set_max_fee_per_gas(max_fee_per_gas);
+            tx.set_max_priority_fee_per_gas(transaction.max_priority_fee_per_gas.unwrap_or_default());
+        } else if access_list.is_some() {
+            tx.set_tx_type(1)?;
+        }
+        if let Some(access_list) = access_list {
+            tx.access_list = Some(access_list);
+        }
 
-        if context.is_ok() {
-            let context = context.unwrap();
-            self.config = context.config;
-            self.alloc = context.alloc;
-            self.env = context.env;
-            self.txs = context.txs;
-            return Ok(())
-        } else {
-            return Err(())
+        ctx.txs = vec![tx];
+        ctx.expect = Some(PostExpectation {
+            test_name: test_name.to_string(),
+            post: state_test.post.clone(),
+            indexes: indexes.clone()
+        });
+        Ok(ctx)
+    }
+
+    pub fn set_work_dir(&mut self, wd: &str) -> bool {
+        if !Path::new(wd).exists() {
+            return false;
         }
+        self.config.work_dir = wd.to_string();
+        true
     }
 
-    pub fn run(&self) {
-      let work_dir = &self.config.work_dir.clone();
+    pub fn save(&self, fname: &str) -> Result<(), Error> {
+        write_json(fname, &self)
+    }
 
-      // Delete previous executions (trace-*)
-      for entry in fs::read_dir(work_dir).unwrap() {
-          let ent = entry.unwrap();
-          let path = ent.path();
-          let fname = path.file_name().unwrap().to_str().unwrap();
+    pub fn load(&mut self, fname: &str) -> Result<(), Error> {
+        let context: Context = read_json(fname)?;
+        self.config = context.config;
+        self.alloc = context.alloc;
+        self.env = context.env;
+        self.txs = context.txs;
+        self.expect = context.expect;
+        self.reward = context.reward;
+        Ok(())
+    }
 
-          if fname.starts_with("trace-") {
-              if fs::remove_file(path.to_str().unwrap()).is_err() {
-                  println!("Error: failed to remove previous trace {}", fname);
-              }
-          }
-      }
+    // Every run uses its own directory below the working directory, so several runs can
+    // happen at the same time
+    pub fn run(&self) -> Result<RunOutput, Error> {
+      let run_dir = RunDir::create(self.config.work_dir.as_str())?;
+      let work_dir = run_dir.path.as_str();
+      let result_file_path = String::from(work_dir) + "/alloc_jsontx.json";
+      let post_alloc_file_path = String::from(work_dir) + "/post_alloc.json";
 
       // Save json files
-      let alloc = &self.alloc.clone();
-      let alloc_str = serde_json::to_string(&alloc).unwrap();
-      let alloc_file_path = work_dir.clone() + "/alloc.json";
-      let mut file = fs::File::create(alloc_file_path).unwrap();
-      file.write(alloc_str.as_bytes()).expect("Error writing alloc.json file");
-
-      let env = &self.env.clone();
-      let env_str = serde_json::to_string(&env).unwrap();
-      let env_file_path = work_dir.clone() + "/env.json";
-      let mut file = fs::File::create(env_file_path).unwrap();
-      file.write(env_str.as_bytes()).expect("Error writing env.json file");
-
-      let txs = &self.txs.clone();
-      let txs_str = serde_json::to_string(&txs).unwrap();
-      let txs_file_path = work_dir.clone() + "/txs.json";
-      let mut file = fs::File::create(txs_file_path).unwrap();
-      file.write(txs_str.as_bytes()).expect("Error writing txs.json file");
+      let alloc_file_path = String::from(work_dir) + "/alloc.json";
+      write_json(alloc_file_path.as_str(), &self.alloc)?;
+
+      let env_file_path = String::from(work_dir) + "/env.json";
+      write_json(env_file_path.as_str(), &self.env.prepared(self.config.hard_fork)?)?;
+
+      let txs_file_path = String::from(work_dir) + "/txs.json";
+      write_json(txs_file_path.as_str(), &self.txs)?;
 
       // Execute t8n tool
-      let fork_flag = String::from("--state.fork=") + &self.config.hard_fork.as_str();
-      let alloc_flag = String::from("--input.alloc=") + work_dir.as_str() + "/alloc.json";
-      let env_flag = String::from("--input.env=") + work_dir.as_str() + "/env.json";
-      let txs_flag = String::from("--input.txs=") + work_dir.as_str() + "/txs.json";
+      let fork_flag = format!("--state.fork={}", self.config.hard_fork);
+      let alloc_flag = String::from("--input.alloc=") + alloc_file_path.as_str();
+      let env_flag = String::from("--input.env=") + env_file_path.as_str();
+      let txs_flag = String::from("--input.txs=") + txs_file_path.as_str();
       let result_flag = String::from("--output.result=") + "alloc_jsontx.json";
+      let post_alloc_flag = String::from("--output.alloc=") + "post_alloc.json";
       let body_flag = String::from("--output.body=") + "/signed_txs.rlp";
-      let basedir_flag = String::from("--output.basedir=") + work_dir.as_str();
+      let basedir_flag = String::from("--output.basedir=") + work_dir;
       let trace_flag = String::from("--trace");
 
       let mut args: Vec<String> = Vec::new();
 
-      if &self.config.evm.as_str() != &"" {
-        let vm_flag = String::from("--vm.evm=") + &self.config.evm.as_str();
+      if !self.config.evm.is_empty() {
+        let vm_flag = String::from("--vm.evm=") + self.config.evm.as_str();
         args.push(vm_flag);
       }
 
@@ -392,41 +1441,73 @@ impl Context {
       args.push(env_flag);
       args.push(txs_flag);
       args.push(result_flag);
+      args.push(post_alloc_flag);
       args.push(body_flag);
       args.push(basedir_flag);
       args.push(trace_flag);
 
-      let mut cmd = Command::new(&self.config.t8n.clone());
-      cmd.args(args.clone());
-
-      let output = cmd.output().unwrap();
-      let output_text = String::from_utf8(output.stdout).unwrap();
-      let error_text = String::from_utf8(output.stderr).unwrap();
-      println!("{}", output_text);
-      println!("{}", error_text);
-
-      let traces: Vec<Trace> = Vec::new();
-      for entry in fs::read_dir(work_dir).unwrap() {
-          let ent = entry.unwrap();
-          let path = ent.path();
-          let fname = path.file_name().unwrap().to_str().unwrap();
-          if fname.to_string().starts_with("trace-") {
-              let trace_file = path.to_str().unwrap();
-              let trace_file = fs::read_to_string(trace_file);
-
-              if trace_file.is_ok() {
-                  let contents = trace_file.unwrap();
-                  let lines = contents.split('\n');
-
-                  for line in lines {
-                      println!("{}", line);
-                  }
-              } else {
-                  println!("Error reading trace file {}", fname)
-              }
+      if let Some(reward) = &self.reward {
+        args.push(format!("--state.reward={}", reward));
+      }
+
+      let mut cmd = Command::new(self.config.t8n.as_str());
+      cmd.args(args);
+
+      let output = cmd.output().map_err(|e| Error::T8nNotFound { path: self.config.t8n.clone(), source: e })?;
+      if !output.status.success() {
+          return Err(Error::T8nExit { code: output.status.code(), stderr: String::from_utf8_lossy(&output.stderr).to_string() });
+      }
 
+      let mut traces: Vec<Trace> = Vec::new();
+      for entry in fs::read_dir(work_dir).map_err(|e| Error::io(work_dir, e))? {
+          let path = entry.map_err(|e| Error::io(work_dir, e))?.path();
+          let is_trace = path.file_name()
+              .and_then(|f| f.to_str())
+              .map(|f| f.starts_with("trace-"))
+              .unwrap_or(false);
+          if is_trace {
+              traces.push(Trace::from_file(&path)?);
           }
       }
+      traces.sort_by_key(|t| t.tx_index);
+
+      let result = ExecutionResult::from_file(result_file_path.as_str())?;
+      let alloc: HashMap<Address, Alloc> = read_json(post_alloc_file_path.as_str())?;
+      Ok(RunOutput { result, alloc, traces })
+    }
+
+    // Compares a t8n result with the state test `post` entry for the configured fork and indexes
+    pub fn verify(&self, result: &ExecutionResult) -> Result<Verification, Error> {
+        let expect = self.expect.as_ref().ok_or_else(|| Error::invalid("Context has no post expectations"))?;
+        if self.txs.len() != 1 {
+            return Err(Error::invalid("Post expectations can only be verified for a single transaction"));
+        }
+        let fork_posts = fork_posts(&expect.post, self.config.hard_fork)
+            .ok_or_else(|| Error::fixture(format!("{}: no post expectations for {}", expect.test_name, self.config.hard_fork)))?;
+        let post = fork_posts.iter()
+            .find(|p| p.indexes == expect.indexes)
+            .ok_or_else(|| Error::fixture(format!("{}: no post expectation for indexes {},{},{}", expect.test_name, expect.indexes.data, expect.indexes.gas, expect.indexes.value)))?;
+
+        let mut mismatches: Vec<Mismatch> = Vec::new();
+        if post.hash != result.state_root {
+            mismatches.push(Mismatch { field: "stateRoot".to_string(), expected: post.hash.to_string(), actual: result.state_root.to_string() });
+        }
+        if post.logs != result.logs_hash {
+            mismatches.push(Mismatch { field: "logsHash".to_string(), expected: post.logs.to_string(), actual: result.logs_hash.to_string() });
+        }
+        let rejected = result.is_rejected(0);
+        match &post.expect_exception {
+            Some(exception) if !rejected => {
+                mismatches.push(Mismatch { field: "exception".to_string(), expected: exception.clone(), actual: "none".to_string() });
+            },
+            None if rejected => {
+                let error = result.rejected.iter().find(|r| r.index == 0).map(|r| r.error.clone()).unwrap_or_default();
+                mismatches.push(Mismatch { field: "exception".to_string(), expected: "none".to_string(), actual: error });
+            },
+            _ => {}
+        }
+
+        Ok(Verification { test_name: expect.test_name.clone(), fork: self.config.hard_fork.to_string(), indexes: expect.indexes.clone(), mismatches })
     }
 
     pub fn print_alloc(&self) {
@@ -444,57 +1525,377 @@ impl Context {
 
 #[derive(Debug, Deserialize, Clone)]
 struct StateTestContent {
-    #[serde(rename="_info")]
+    #[serde(rename="_info", default)]
+    #[allow(dead_code)]
     info : Info,
     env : Env,
     post: HashMap<String, Vec<Post>>,
-    pre: HashMap<String, Alloc>,
+    pre: HashMap<Address, Alloc>,
     transaction: Transaction
 }
 
-#[derive(Deserialize, Debug, Clone)]
+// Fillers disagree on which `_info` fields they emit, none of them is required
+#[derive(Deserialize, Debug, Clone, Default)]
+#[allow(dead_code)]
 struct Info {
-    comment: String,
+    comment: Option<String>,
     #[serde(rename="filling-rpc-server")]
-    filling_rpc_server : String,
+    filling_rpc_server : Option<String>,
     #[serde(alias="filling-tool-version")]
-    filling_tool_version : String,
+    filling_tool_version : Option<String>,
     #[serde(alias="generatedTestHash")]
-    generated_test_hash : String,
-    lllcversion : String,
-    source: String,
+    generated_test_hash : Option<String>,
+    lllcversion : Option<String>,
+    source: Option<String>,
     #[serde(alias="sourceHash")]
-    source_hash : String
+    source_hash : Option<String>
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct Post {
-    hash : String,
-    indexes: Indexes,
-    logs: String,
-    txbytes: String
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Post {
+    pub hash : B256,
+    pub indexes: Indexes,
+    pub logs: B256,
+    pub txbytes: Option<Bytes>,
+    #[serde(rename="expectException")]
+    pub expect_exception: Option<String>
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct Transaction {
-    data : Vec<String>,
+    data : Vec<Bytes>,
     #[serde(alias="gasLimit")]
-    gas_limit : Vec<String>,
+    gas_limit : Vec<U256>,
     #[serde(alias="gasPrice")]
-    gas_price: String,
-    nonce: String,
+    gas_price: Option<U256>,
+    #[serde(rename="maxFeePerGas")]
+    max_fee_per_gas: Option<U256>,
+    #[serde(rename="maxPriorityFeePerGas")]
+    max_priority_fee_per_gas: Option<U256>,
+    // One access list per `data` entry
+    #[serde(rename="accessLists")]
+    access_lists: Option<Vec<Option<Vec<AccessListItem>>>>,
+    #[serde(rename="maxFeePerBlobGas")]
+    max_fee_per_blob_gas: Option<U256>,
+    #[serde(rename="blobVersionedHashes")]
+    blob_versioned_hashes: Option<Vec<B256>>,
+    #[serde(rename="authorizationList")]
+    authorization_list: Option<Vec<Authorization>>,
+    nonce: U256,
     #[serde(alias="secretKey")]
-    secret_key: String,
-    sender: String,
-    to: String,
-    value : Vec<String>
+    secret_key: B256,
+    #[allow(dead_code)]
+    sender: Option<Address>,
+    #[serde(deserialize_with="empty_string_as_none")]
+    to: Option<Address>,
+    value : Vec<U256>
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct Indexes {
-    data : u64,
-    gas : u64,
-    value : u64,
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Indexes {
+    pub data : u64,
+    pub gas : u64,
+    pub value : u64,
 }
 
+impl Indexes {
+    // Parses a `data,gas,value` tuple, e.g. `1,0,0`
+    pub fn parse(indexes: &str) -> Option<Indexes> {
+        let parts: Vec<u64> = indexes.split(',').map(|p| p.trim().parse::<u64>()).collect::<Result<_, _>>().ok()?;
+        if parts.len() != 3 {
+            return None;
+        }
+        Some(Indexes { data: parts[0], gas: parts[1], value: parts[2] })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Transaction of the EIP-155 example, signed with 0x4646...46
+    fn eip155_transaction() -> TransactionT8n {
+        let mut tx = TransactionT8n::new(
+            Bytes::default(),
+            U256::from(21000),
+            U256::from(20_000_000_000),
+            U256::from(9),
+            Some(Address([0x35; 20])),
+            U256::from(1_000_000_000_000_000_000),
+            B256([0x46; 32]),
+            Some(U256::ZERO));
+        tx.chain_id = U256::from(1);
+        tx
+    }
+
+    #[test]
+    fn eip155_signing_hash() {
+        let tx = eip155_transaction();
+        let expected: B256 = "0xdaf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53".parse().unwrap();
+        assert_eq!(tx.signing_hash(true).unwrap(), expected);
+        assert_ne!(tx.signing_hash(false).unwrap(), expected);
+    }
+
+    #[test]
+    fn signs_eip155_example() {
+        let mut tx = eip155_transaction();
+        let sender = tx.sign(HardFork::EIP158).unwrap();
+        assert_eq!(tx.v, U256::from(37));
+        assert_eq!(tx.r, "18515461264373351373200002665853028612451056578545711640558177340181847433846".parse().unwrap());
+        assert_eq!(tx.s, "46948507304638947509940763649030358759909902576025900602547168820602576006531".parse().unwrap());
+        assert!(!tx.has_secret_key());
+        assert_eq!(tx.sender().unwrap(), Some(sender));
+        assert_eq!(sender, crypto::secret_key_address(&B256([0x46; 32])).unwrap());
+    }
+
+    #[test]
+    fn signs_legacy_without_chain_id_before_eip158() {
+        let mut tx = eip155_transaction();
+        let sender = tx.sign(HardFork::Homestead).unwrap();
+        assert!(tx.v == U256::from(27) || tx.v == U256::from(28));
+        assert_eq!(tx.sender().unwrap(), Some(sender));
+    }
+
+    #[test]
+    fn signs_typed_transactions_with_recovery_id() {
+        let mut tx = eip155_transaction();
+        tx.set_tx_type(2).unwrap();
+        let sender = tx.sign(HardFork::London).unwrap();
+        assert!(tx.v <= U256::from(1));
+        assert_eq!(tx.sender().unwrap(), Some(sender));
+    }
+
+    #[test]
+    fn signs_with_large_chain_ids() {
+        let mut tx = eip155_transaction();
+        tx.chain_id = U256::from(u64::MAX);
+        let sender = tx.sign(HardFork::EIP158).unwrap();
+        assert!(tx.v.to_u64().is_none());
+        assert_eq!(tx.sender().unwrap(), Some(sender));
+
+        let mut tx = eip155_transaction();
+        tx.chain_id = "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".parse().unwrap();
+        assert!(tx.sign(HardFork::EIP158).is_err());
+    }
+
+    fn base_fee_env(number: u64, parent_base_fee: U256, parent_gas_used: u64) -> Env {
+        let mut env = Env { current_number: U256::from(number), ..Env::default() };
+        env.set_parent_gas(parent_base_fee, U256::from(parent_gas_used), U256::from(20_000_000));
+        env
+    }
+
+    #[test]
+    fn base_fee_follows_parent_gas_usage() {
+        let fee = U256::from(1000);
+        assert_eq!(base_fee_env(10, fee, 10_000_000).base_fee(HardFork::London).unwrap(), Some(fee));
+        assert_eq!(base_fee_env(10, fee, 20_000_000).base_fee(HardFork::London).unwrap(), Some(U256::from(1125)));
+        assert_eq!(base_fee_env(10, fee, 0).base_fee(HardFork::London).unwrap(), Some(U256::from(875)));
+        // The increase is at least 1 wei
+        assert_eq!(base_fee_env(10, fee, 10_000_001).base_fee(HardFork::London).unwrap(), Some(U256::from(1001)));
+    }
+
+    #[test]
+    fn base_fee_by_fork() {
+        let fee = U256::from(1000);
+        assert_eq!(base_fee_env(10, fee, 0).base_fee(HardFork::Berlin).unwrap(), None);
+        // First London block of a transition fork, the parent is a Berlin block
+        assert_eq!(base_fee_env(5, fee, 0).base_fee(HardFork::BerlinToLondonAt5).unwrap(), Some(U256::from(1_000_000_000)));
+        assert_eq!(base_fee_env(6, fee, 0).base_fee(HardFork::BerlinToLondonAt5).unwrap(), Some(U256::from(875)));
+        assert_eq!(Env::default().base_fee(HardFork::London).unwrap(), Some(U256::from(0x0a)));
+        let mut incomplete = base_fee_env(10, fee, 0);
+        incomplete.parent_gas_limit = None;
+        assert_eq!(incomplete.base_fee(HardFork::London).unwrap(), None);
+    }
+
+    #[test]
+    fn base_fee_overflow_is_an_error() {
+        let max: U256 = "0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".parse().unwrap();
+        assert!(base_fee_env(10, max, 20_000_000).base_fee(HardFork::London).is_err());
+    }
+
+    fn difficulty_env(number: u64, elapsed: u64, uncles: bool) -> Env {
+        let mut env = Env { current_number: U256::from(number), current_timestamp: U256::from(100_000), ..Env::default() };
+        let uncle_hash = if uncles { B256([0x01; 32]) } else { EMPTY_UNCLE_HASH };
+        env.set_parent_difficulty(U256::from(1_000_000), env.current_timestamp - U256::from(elapsed), Some(uncle_hash));
+        env
+    }
+
+    fn difficulty(fork: HardFork, number: u64, elapsed: u64, uncles: bool) -> u64 {
+        difficulty_env(number, elapsed, uncles).difficulty(fork).unwrap().unwrap().to_u64().unwrap()
+    }
+
+    #[test]
+    fn difficulty_adjustment_by_fork() {
+        // 1_000_000 / 2048 = 488 per adjustment step
+        assert_eq!(difficulty(HardFork::Frontier, 1, 12, false), 1_000_488);
+        assert_eq!(difficulty(HardFork::Frontier, 1, 13, false), 999_512);
+        assert_eq!(difficulty(HardFork::Homestead, 1, 9, false), 1_000_488);
+        assert_eq!(difficulty(HardFork::Homestead, 1, 10, false), 1_000_000);
+        assert_eq!(difficulty(HardFork::Homestead, 1, 20, false), 999_512);
+        assert_eq!(difficulty(HardFork::Homestead, 1, 2000, false), 1_000_000 - 99 * 488);
+        assert_eq!(difficulty(HardFork::Byzantium, 1, 9, false), 1_000_000);
+        assert_eq!(difficulty(HardFork::Byzantium, 1, 9, true), 1_000_488);
+        assert_eq!(difficulty(HardFork::Merge, 1, 9, true), 0);
+    }
+
+    #[test]
+    fn difficulty_has_a_minimum() {
+        let mut env = difficulty_env(1, 100, false);
+        env.parent_difficulty = Some(U256::from(131072));
+        assert_eq!(env.difficulty(HardFork::Homestead).unwrap(), Some(U256::from(131072)));
+    }
+
+    #[test]
+    fn difficulty_bomb_delays() {
+        // Without delay the bomb adds 2^(number / 100000 - 2)
+        assert_eq!(difficulty(HardFork::Homestead, 199_999, 10, false), 1_000_000);
+        assert_eq!(difficulty(HardFork::Homestead, 200_000, 10, false), 1_000_001);
+        assert_eq!(difficulty(HardFork::Homestead, 500_000, 10, false), 1_000_008);
+        let delays = [
+            (HardFork::Byzantium, 3_000_000),
+            (HardFork::Constantinople, 5_000_000),
+            (HardFork::MuirGlacier, 9_000_000),
+            (HardFork::London, 9_700_000),
+            (HardFork::ArrowGlacier, 10_700_000),
+            (HardFork::GrayGlacier, 11_400_000)
+        ];
+        for (fork, delay) in delays {
+            assert_eq!(difficulty(fork, delay + 199_999, 9, false), 1_000_000, "{}", fork);
+            assert_eq!(difficulty(fork, delay + 200_000, 9, false), 1_000_001, "{}", fork);
+        }
+    }
+
+    #[test]
+    fn explicit_difficulty_is_kept() {
+        let mut env = difficulty_env(1, 9, false);
+        env.set_current_difficulty(U256::from(42));
+        assert_eq!(env.difficulty(HardFork::Byzantium).unwrap(), Some(U256::from(42)));
+    }
+
+    #[test]
+    fn difficulty_overflow_is_an_error() {
+        let mut env = difficulty_env(1, 0, false);
+        env.parent_difficulty = Some("0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff".parse().unwrap());
+        assert!(env.difficulty(HardFork::Homestead).is_err());
+        // The bomb of block 25_800_000 would be 2^256
+        assert!(difficulty_env(25_799_999, 10, false).difficulty(HardFork::Homestead).is_ok());
+        assert!(difficulty_env(25_800_000, 10, false).difficulty(HardFork::Homestead).is_err());
+    }
+
+    #[test]
+    fn authorization_nonce_of_the_sender() {
+        let mut ctx = Context::with_config(Config { work_dir: String::new(), t8n: String::new(), evm: String::new(), hard_fork: HardFork::Prague });
+        let authority = ctx.add_secret_key(B256([0x46; 32])).unwrap();
+        let mut tx = TransactionT8n::default();
+        tx.set_private_key(B256([0x46; 32]));
+        ctx.txs.push(tx);
+        let delegate = Address::from_low_u64(0x100);
+
+        ctx.alloc.get_mut(&authority).unwrap().nonce = U256::from(7);
+        // The sender nonce is incremented before the authorization list is processed
+        assert_eq!(ctx.authorize(0, &authority, delegate, None).unwrap().nonce, U256::from(8));
+        assert_eq!(ctx.authorize(0, &authority, delegate, Some(U256::from(3))).unwrap().nonce, U256::from(3));
+
+        ctx.alloc.get_mut(&authority).unwrap().nonce = U256::from(u64::MAX);
+        assert!(ctx.authorize(0, &authority, delegate, None).is_err());
+    }
 
+    // Writes `contents` to a file named `name` in a fresh directory below the system
+    // temporary directory and returns its path
+    fn temp_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("t8n-context-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_traces_with_hex_and_numeric_quantities() {
+        let lines = [
+            // geth
+            r#"{"pc":0,"op":96,"gas":"0x2540be400","gasCost":"0x3","memSize":0,"stack":[],"depth":1,"refund":0,"opName":"PUSH1"}"#,
+            "",
+            // evmone and besu write numbers and decimal strings
+            r#"{"pc":2,"op":1,"gas":9999999997,"gasCost":"3","memSize":0,"stack":["0x1","0x2"],"depth":1,"refund":"0x12c","returnData":"0x"}"#,
+            r#"{"output":"","gasUsed":"0x5208","pass":true,"time":1200}"#
+        ];
+        let path = temp_file("trace-1-0xabcd.jsonl", &lines.join("\n"));
+        let trace = Trace::from_file(&path).unwrap();
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        assert_eq!((trace.tx_index, trace.tx_hash.as_str()), (1, "0xabcd"));
+        assert_eq!(trace.steps.len(), 2);
+        assert_eq!((trace.steps[0].gas, trace.steps[0].gas_cost), (10_000_000_000, 3));
+        assert_eq!(trace.steps[0].op_name.as_deref(), Some("PUSH1"));
+        assert_eq!((trace.steps[1].gas, trace.steps[1].gas_cost, trace.steps[1].refund), (9_999_999_997, 3, 300));
+        assert_eq!(trace.steps[1].stack, vec![U256::from(1), U256::from(2)]);
+        let summary = trace.summary.unwrap();
+        assert_eq!(summary.gas_used, 21000);
+        assert_eq!(summary.pass, Some(true));
+    }
+
+    #[test]
+    fn reports_bad_trace_lines_with_their_number() {
+        let lines = [
+            r#"{"pc":0,"op":96,"gas":"0x10","gasCost":"0x3","depth":1}"#,
+            "",
+            r#"{"pc":2,"op":1,"gas":"0xzz","gasCost":"0x3","depth":1}"#
+        ];
+        let path = temp_file("trace-0-0x01.jsonl", &lines.join("\n"));
+        let bad_quantity = Trace::from_file(&path);
+        fs::write(&path, "{\"pc\":0,\n").unwrap();
+        let bad_json = Trace::from_file(&path);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        assert!(matches!(bad_quantity, Err(Error::Json { line: 3, .. })));
+        assert!(matches!(bad_json, Err(Error::Json { line: 1, .. })));
+        assert!(matches!(Trace::from_file(Path::new("trace-x-0x01.jsonl")), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn parses_t8n_results() {
+        let result: ExecutionResult = serde_json::from_str(r#"{
+            "stateRoot": "0x1ed5c6bd6e5ec1d8e2ff0a2cd6d3c1ed2c3bb1d9ac3d1e5f8c0c54c2f2c6a0a1",
+            "txRoot": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "receiptsRoot": "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "logsHash": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+            "logsBloom": "0x00",
+            "receipts": [{
+                "type": "0x2",
+                "root": "0x",
+                "status": "0x1",
+                "cumulativeGasUsed": "0x5208",
+                "logsBloom": "0x00",
+                "logs": null,
+                "transactionHash": "0x0000000000000000000000000000000000000000000000000000000000000003",
+                "contractAddress": "0x0000000000000000000000000000000000000000",
+                "gasUsed": "0x5208",
+                "effectiveGasPrice": null,
+                "blockHash": "0x0000000000000000000000000000000000000000000000000000000000000000",
+                "transactionIndex": "0x0"
+            }],
+            "rejected": [{ "index": 1, "error": "nonce too low: address 0x01, tx: 0 state: 1" }],
+            "currentDifficulty": null,
+            "gasUsed": "0x5208",
+            "currentBaseFee": "0x7",
+            "withdrawalsRoot": "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+        }"#).unwrap();
+        assert_eq!(result.gas_used, U256::from(21000));
+        assert_eq!(result.current_base_fee, Some(U256::from(7)));
+        assert_eq!(result.current_difficulty, None);
+        assert_eq!(result.receipts.len(), 1);
+        assert_eq!(result.receipts[0].status, U256::from(1));
+        assert_eq!(result.receipts[0].tx_type, Some(U256::from(2)));
+        assert!(result.is_rejected(1));
+        assert!(!result.is_rejected(0));
+        assert!(result.blob_gas_used.is_none());
+
+        let missing_field = serde_json::from_str::<ExecutionResult>(r#"{ "stateRoot": "0x00" }"#);
+        assert!(missing_field.is_err());
+    }
+}
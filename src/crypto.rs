@@ -0,0 +1,52 @@
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use rand_core::OsRng;
+use sha3::{Digest, Keccak256};
+
+use crate::error::Error;
+use crate::types::{Address, B256, U256};
+
+pub fn keccak256(data: &[u8]) -> B256 {
+    B256(Keccak256::digest(data).into())
+}
+
+fn signing_key(secret_key: &B256) -> Result<SigningKey, Error> {
+    SigningKey::from_bytes(&secret_key.0.into()).map_err(|_| Error::invalid(format!("Invalid secret key {}", secret_key)))
+}
+
+fn public_key_address(key: &VerifyingKey) -> Address {
+    let public_key = key.to_encoded_point(false);
+    let hash = keccak256(&public_key.as_bytes()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash.0[12..]);
+    Address(address)
+}
+
+// Address of the account controlled by `secret_key`: last 20 bytes of the keccak of the public key
+pub fn secret_key_address(secret_key: &B256) -> Result<Address, Error> {
+    Ok(public_key_address(signing_key(secret_key)?.verifying_key()))
+}
+
+pub fn random_secret_key() -> B256 {
+    B256(SigningKey::random(&mut OsRng).to_bytes().into())
+}
+
+// Signs a 32 byte hash, returns (recovery id, r, s) with `s` in the lower half of the curve order
+pub fn sign_hash(secret_key: &B256, hash: &B256) -> Result<(u64, U256, U256), Error> {
+    let (signature, recovery_id) = signing_key(secret_key)?
+        .sign_prehash_recoverable(&hash.0)
+        .map_err(|e| Error::invalid(format!("Cannot sign transaction: {}", e)))?;
+    let bytes = signature.to_bytes();
+    Ok((recovery_id.to_byte() as u64, U256::from_be_bytes(&bytes[..32]), U256::from_be_bytes(&bytes[32..])))
+}
+
+// Address of the key that signed `hash`, the inverse of `sign_hash`
+pub fn recover_address(hash: &B256, recovery_id: u64, r: &U256, s: &U256) -> Result<Address, Error> {
+    let invalid = || Error::invalid("Invalid signature");
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&r.to_be_bytes());
+    bytes[32..].copy_from_slice(&s.to_be_bytes());
+    let signature = Signature::from_slice(&bytes).map_err(|_| invalid())?;
+    let recovery_id = u8::try_from(recovery_id).ok().and_then(RecoveryId::from_byte).ok_or_else(invalid)?;
+    let key = VerifyingKey::recover_from_prehash(&hash.0, &signature, recovery_id).map_err(|_| invalid())?;
+    Ok(public_key_address(&key))
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::thread;
+
+use crate::config::Config;
+use crate::context::{Alloc, Context, ExecutionResult, Receipt, RunOutput};
+use crate::error::Error;
+use crate::trace_diff::{self, TraceDivergence};
+use crate::types::{Address, U256};
+
+// A value reported differently by the two backends
+#[derive(Debug, Clone)]
+pub struct Difference {
+    pub field: String,
+    pub left: String,
+    pub right: String
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffReport {
+    pub left: String,
+    pub right: String,
+    pub differences: Vec<Difference>,
+    pub trace_divergence: Option<TraceDivergence>
+}
+
+fn backend_name(config: &Config) -> String {
+    if config.evm.is_empty() {
+        config.t8n.clone()
+    } else {
+        format!("{} ({})", config.t8n, config.evm)
+    }
+}
+
+impl DiffReport {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty() && self.trace_divergence.is_none()
+    }
+
+    pub fn print(&self) {
+        if self.is_empty() {
+            println!("No differences between {} and {}", self.left, self.right);
+            return;
+        }
+        println!("Differences between {} (left) and {} (right):", self.left, self.right);
+        for d in &self.differences {
+            println!("\t{}: {} != {}", d.field, d.left, d.right);
+        }
+        if let Some(divergence) = &self.trace_divergence {
+            divergence.print();
+        }
+    }
+}
+
+fn push<T: PartialEq + ToString>(differences: &mut Vec<Difference>, field: impl Into<String>, left: &T, right: &T) {
+    if left != right {
+        differences.push(Difference { field: field.into(), left: left.to_string(), right: right.to_string() });
+    }
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "none".to_string())
+}
+
+fn compare_results(left: &ExecutionResult, right: &ExecutionResult, differences: &mut Vec<Difference>) {
+    push(differences, "stateRoot", &left.state_root, &right.state_root);
+    push(differences, "txRoot", &left.tx_root, &right.tx_root);
+    push(differences, "receiptsRoot", &left.receipts_root, &right.receipts_root);
+    push(differences, "logsHash", &left.logs_hash, &right.logs_hash);
+    push(differences, "gasUsed", &left.gas_used, &right.gas_used);
+    push(differences, "withdrawalsRoot", &optional(&left.withdrawals_root), &optional(&right.withdrawals_root));
+    push(differences, "blobGasUsed", &optional(&left.blob_gas_used), &optional(&right.blob_gas_used));
+
+    let rejected = |result: &ExecutionResult| {
+        let mut indexes: Vec<u64> = result.rejected.iter().map(|r| r.index).collect();
+        indexes.sort();
+        format!("{:?}", indexes)
+    };
+    push(differences, "rejected", &rejected(left), &rejected(right));
+
+    for ix in 0..left.receipts.len().max(right.receipts.len()) {
+        let (l, r) = match (left.receipts.get(ix), right.receipts.get(ix)) {
+            (Some(l), Some(r)) => (l, r),
+            (l, r) => {
+                let describe = |receipt: Option<&Receipt>| receipt.map(|r| format!("tx {}", r.transaction_index)).unwrap_or_else(|| "missing".to_string());
+                push(differences, format!("receipt {}", ix), &describe(l), &describe(r));
+                continue;
+            }
+        };
+        let field = |name: &str| format!("receipt {} {}", ix, name);
+        push(differences, field("transactionHash"), &l.transaction_hash, &r.transaction_hash);
+        push(differences, field("status"), &l.status, &r.status);
+        push(differences, field("gasUsed"), &l.gas_used, &r.gas_used);
+        push(differences, field("cumulativeGasUsed"), &l.cumulative_gas_used, &r.cumulative_gas_used);
+        push(differences, field("logsBloom"), &l.logs_bloom, &r.logs_bloom);
+        push(differences, field("contractAddress"), &optional(&l.contract_address), &optional(&r.contract_address));
+        let logs = |receipt: &Receipt| receipt.logs.as_ref().map(|l| l.len()).unwrap_or(0);
+        push(differences, field("logs"), &logs(l), &logs(r));
+    }
+}
+
+fn compare_allocs(left: &HashMap<Address, Alloc>, right: &HashMap<Address, Alloc>, differences: &mut Vec<Difference>) {
+    let mut addresses: Vec<&Address> = left.keys().chain(right.keys()).collect();
+    addresses.sort();
+    addresses.dedup();
+    for address in addresses {
+        let (l, r) = match (left.get(address), right.get(address)) {
+            (Some(l), Some(r)) => (l, r),
+            (l, _) => {
+                let (left, right) = if l.is_some() { ("account", "missing") } else { ("missing", "account") };
+                push(differences, address.to_string(), &left, &right);
+                continue;
+            }
+        };
+        push(differences, format!("{} balance", address), &l.balance, &r.balance);
+        push(differences, format!("{} nonce", address), &l.nonce, &r.nonce);
+        push(differences, format!("{} code", address), &l.code, &r.code);
+        let mut slots: Vec<&U256> = l.storage.keys().chain(r.storage.keys()).collect();
+        slots.sort();
+        slots.dedup();
+        for slot in slots {
+            let left_value = l.storage.get(slot).copied().unwrap_or_default();
+            let right_value = r.storage.get(slot).copied().unwrap_or_default();
+            push(differences, format!("{} storage {}", address, slot), &left_value, &right_value);
+        }
+    }
+}
+
+// Steps shown around the first trace divergence
+const TRACE_CONTEXT: usize = 3;
+
+// Differences between the outputs of two backends for the same context
+pub fn compare(left: &RunOutput, right: &RunOutput, left_name: &str, right_name: &str) -> DiffReport {
+    let mut differences: Vec<Difference> = Vec::new();
+    compare_results(&left.result, &right.result, &mut differences);
+    compare_allocs(&left.alloc, &right.alloc, &mut differences);
+    DiffReport {
+        left: left_name.to_string(),
+        right: right_name.to_string(),
+        differences,
+        trace_divergence: trace_diff::first_block_divergence(&left.traces, &right.traces, TRACE_CONTEXT)
+    }
+}
+
+impl Context {
+    // Runs the context with its own backend and with the t8n tool and evm of `other`, both
+    // at the same time, and compares the outputs
+    pub fn diff_run(&self, other: &Config) -> Result<(RunOutput, RunOutput, DiffReport), Error> {
+        let mut right_ctx = self.clone();
+        right_ctx.config.t8n = other.t8n.clone();
+        right_ctx.config.evm = other.evm.clone();
+        let (left, right) = thread::scope(|scope| {
+            let right = scope.spawn(|| right_ctx.run());
+            let left = self.run();
+            (left, right.join().unwrap_or_else(|_| Err(Error::invalid("t8n run panicked"))))
+        });
+        let (left, right) = (left?, right?);
+        let report = compare(&left, &right, &backend_name(&self.config), &backend_name(&right_ctx.config));
+        Ok((left, right, report))
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use crate::fork::HardFork;
+use crate::opcode::Opcode;
+
+// Decoded instruction, `opcode` is None for bytes not defined in the fork
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub pc: usize,
+    pub byte: u8,
+    pub opcode: Option<Opcode>,
+    // Immediate data, shorter than the opcode requires when the code ends in the middle of it
+    pub immediate: Vec<u8>
+}
+
+impl Instruction {
+    pub fn is_jumpdest(&self) -> bool {
+        self.byte == 0x5b && self.opcode.is_some()
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.opcode.is_some_and(|o| self.immediate.len() < o.immediate)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = if self.is_jumpdest() { "=>" } else { "" };
+        write!(f, "{:<2}{:>6}: ", marker, self.pc)?;
+        match &self.opcode {
+            Some(opcode) => write!(f, "{}", opcode.name)?,
+            None => write!(f, "INVALID (0x{:02x})", self.byte)?
+        }
+        if !self.immediate.is_empty() {
+            write!(f, " 0x{}", hex::encode(&self.immediate))?;
+        }
+        if self.is_truncated() {
+            write!(f, " (truncated)")?;
+        }
+        Ok(())
+    }
+}
+
+// Decodes code with the opcodes of `fork`. PUSH data is never decoded as instructions
+pub fn disassemble(code: &[u8], fork: HardFork) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let byte = code[pc];
+        let opcode = Opcode::at_fork(byte, fork);
+        let size = opcode.map(|o| o.immediate).unwrap_or(0);
+        let end = (pc + 1 + size).min(code.len());
+        instructions.push(Instruction { pc, byte, opcode, immediate: code[pc + 1..end].to_vec() });
+        pc += 1 + size;
+    }
+    instructions
+}
+
+// One instruction per line, `pc: OPCODE immediate` with JUMPDESTs marked
+pub fn listing(code: &[u8], fork: HardFork) -> String {
+    disassemble(code, fork).iter().map(|i| i.to_string()).collect::<Vec<String>>().join("\n")
+}
@@ -0,0 +1,820 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::error::Error;
+use crate::opcode::Opcode;
+use crate::types::Bytes;
+
+pub const MAGIC: [u8; 2] = [0xef, 0x00];
+pub const VERSION: u8 = 0x01;
+// Outputs of a code section that never returns to its caller
+pub const NON_RETURNING: u8 = 0x80;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_CONTAINER: u8 = 0x03;
+const KIND_DATA: u8 = 0x04;
+const TERMINATOR: u8 = 0x00;
+
+const MAX_CODE_SECTIONS: usize = 1024;
+const MAX_CONTAINER_SECTIONS: usize = 256;
+const MAX_STACK_HEIGHT: usize = 1024;
+const MAX_STACK_INCREASE: u16 = 0x03ff;
+const MAX_SECTION_IO: u8 = 0x7f;
+
+const STOP: u8 = 0x00;
+const DATALOADN: u8 = 0xd1;
+const RJUMP: u8 = 0xe0;
+const RJUMPI: u8 = 0xe1;
+const RJUMPV: u8 = 0xe2;
+const CALLF: u8 = 0xe3;
+const RETF: u8 = 0xe4;
+const JUMPF: u8 = 0xe5;
+const DUPN: u8 = 0xe6;
+const SWAPN: u8 = 0xe7;
+const EXCHANGE: u8 = 0xe8;
+const EOFCREATE: u8 = 0xec;
+const RETURNCONTRACT: u8 = 0xee;
+const RETURN: u8 = 0xf3;
+
+// Legacy opcodes that are not allowed in EOF code
+const REJECTED: [u8; 16] = [0x38, 0x39, 0x3b, 0x3c, 0x3f, 0x56, 0x57, 0x58, 0x5a, 0xf0, 0xf1, 0xf2, 0xf4, 0xf5, 0xfa, 0xff];
+
+// Opcodes only defined in EOF code: name, immediate size (RJUMPV adds its jump table),
+// stack inputs and outputs. CALLF, RETF, JUMPF, DUPN, SWAPN and EXCHANGE use the stack
+// according to their immediates and are handled by the stack validation
+const EOF_OPCODES: [(u8, &str, usize, usize, usize); 19] = [
+    (0xd0, "DATALOAD", 0, 1, 1), (DATALOADN, "DATALOADN", 2, 0, 1), (0xd2, "DATASIZE", 0, 0, 1), (0xd3, "DATACOPY", 0, 3, 0),
+    (RJUMP, "RJUMP", 2, 0, 0), (RJUMPI, "RJUMPI", 2, 1, 0), (RJUMPV, "RJUMPV", 1, 1, 0), (CALLF, "CALLF", 2, 0, 0),
+    (RETF, "RETF", 0, 0, 0), (JUMPF, "JUMPF", 2, 0, 0), (DUPN, "DUPN", 1, 0, 0), (SWAPN, "SWAPN", 1, 0, 0),
+    (EXCHANGE, "EXCHANGE", 1, 0, 0), (EOFCREATE, "EOFCREATE", 1, 4, 1), (RETURNCONTRACT, "RETURNCONTRACT", 1, 2, 0),
+    (0xf7, "RETURNDATALOAD", 0, 1, 1), (0xf8, "EXTCALL", 0, 4, 1), (0xf9, "EXTDELEGATECALL", 0, 3, 1), (0xfb, "EXTSTATICCALL", 0, 3, 1)
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EofError {
+    // The header is truncated or its section kinds, counts or sizes are not valid
+    Header(String),
+    // The body does not match the header, or a type or container rule is broken
+    Section(String),
+    // Invalid instruction, immediate or jump in a code section
+    Code { section: usize, pc: Option<usize>, message: String },
+    // Stack underflow, overflow or height mismatch in a code section
+    Stack { section: usize, pc: Option<usize>, message: String },
+    // Error in a nested container
+    Subcontainer { index: usize, error: Box<EofError> }
+}
+
+fn location(section: usize, pc: &Option<usize>) -> String {
+    match pc {
+        Some(pc) => format!("code section {} at pc {}", section, pc),
+        None => format!("code section {}", section)
+    }
+}
+
+impl fmt::Display for EofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EofError::Header(message) => write!(f, "header: {}", message),
+            EofError::Section(message) => write!(f, "{}", message),
+            EofError::Code { section, pc, message } => write!(f, "{}: {}", location(*section, pc), message),
+            EofError::Stack { section, pc, message } => write!(f, "stack validation of {}: {}", location(*section, pc), message),
+            EofError::Subcontainer { index, error } => write!(f, "subcontainer {}: {}", index, error)
+        }
+    }
+}
+
+impl From<EofError> for Error {
+    fn from(e: EofError) -> Error {
+        Error::invalid(format!("invalid EOF container, {}", e))
+    }
+}
+
+fn code_error(section: usize, pc: usize, message: impl Into<String>) -> EofError {
+    EofError::Code { section, pc: Some(pc), message: message.into() }
+}
+
+fn stack_error(section: usize, pc: usize, message: impl Into<String>) -> EofError {
+    EofError::Stack { section, pc: Some(pc), message: message.into() }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeSection {
+    pub inputs: u8,
+    pub outputs: u8,
+    // Stack items the section uses above its inputs
+    pub max_stack_increase: u16
+}
+
+impl TypeSection {
+    pub fn is_returning(&self) -> bool {
+        self.outputs != NON_RETURNING
+    }
+}
+
+// EOF (EIP-3540) container. Subcontainers are kept encoded and parsed when validated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    pub types: Vec<TypeSection>,
+    pub code: Vec<Vec<u8>>,
+    pub containers: Vec<Vec<u8>>,
+    pub data: Vec<u8>,
+    // Declared in the header, larger than `data` when the data section is truncated
+    pub data_size: u16
+}
+
+pub fn is_eof(code: &[u8]) -> bool {
+    code.starts_with(&MAGIC)
+}
+
+// Parses and validates code deployed to an account
+pub fn validate(code: &[u8]) -> Result<Container, EofError> {
+    let container = Container::parse(code)?;
+    container.validate()?;
+    Ok(container)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn header(&mut self, n: usize, what: &str) -> Result<usize, EofError> {
+        let bytes = self.take(n).ok_or_else(|| EofError::Header(format!("truncated reading {}", what)))?;
+        Ok(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    }
+
+    fn body(&mut self, n: usize, what: &str) -> Result<&'a [u8], EofError> {
+        self.take(n).ok_or_else(|| EofError::Section(format!("container truncated in {}", what)))
+    }
+}
+
+fn expect_kind(reader: &mut Reader, kind: u8, name: &str) -> Result<(), EofError> {
+    let found = reader.header(1, &format!("{} section kind", name))?;
+    if found != kind as usize {
+        return Err(EofError::Header(format!("expected {} section kind 0x{:02x}, found 0x{:02x}", name, kind, found)));
+    }
+    Ok(())
+}
+
+// Immediate bytes of the instruction at `pc`, None when the opcode is not valid in EOF.
+// The size can go past the end of the code
+fn immediate_size(code: &[u8], pc: usize) -> Option<usize> {
+    let op = code[pc];
+    if let Some((_, _, size, _, _)) = EOF_OPCODES.iter().find(|o| o.0 == op) {
+        if op == RJUMPV {
+            return Some(code.get(pc + 1).map(|max_index| 1 + 2 * (*max_index as usize + 1)).unwrap_or(1));
+        }
+        return Some(*size);
+    }
+    if REJECTED.contains(&op) {
+        return None;
+    }
+    Opcode::from_byte(op).map(|o| o.immediate)
+}
+
+// Name of an opcode valid in EOF code
+pub fn opcode_name(op: u8) -> Option<&'static str> {
+    match EOF_OPCODES.iter().find(|o| o.0 == op) {
+        Some((_, name, ..)) => Some(*name),
+        None if op == 0x5b => Some("NOP"),
+        None if REJECTED.contains(&op) => None,
+        None => Opcode::from_byte(op).map(|o| o.name)
+    }
+}
+
+// Stack inputs and outputs of the opcodes with a fixed stack use
+fn stack_io(op: u8) -> (usize, usize) {
+    if let Some((_, _, _, inputs, outputs)) = EOF_OPCODES.iter().find(|o| o.0 == op) {
+        return (*inputs, *outputs);
+    }
+    match op {
+        0x01..=0x07 | 0x0a | 0x0b | 0x10..=0x14 | 0x16..=0x18 | 0x1a..=0x1d | 0x20 => (2, 1),
+        0x08 | 0x09 => (3, 1),
+        0x15 | 0x19 | 0x1e | 0x31 | 0x35 | 0x40 | 0x49 | 0x51 | 0x54 | 0x5c => (1, 1),
+        0x30 | 0x32..=0x34 | 0x36 | 0x3a | 0x3d | 0x41..=0x48 | 0x4a | 0x59 | 0x5f..=0x7f => (0, 1),
+        0x37 | 0x3e | 0x5e => (3, 0),
+        0x50 => (1, 0),
+        0x52 | 0x53 | 0x55 | 0x5d | 0xf3 | 0xfd => (2, 0),
+        0x80..=0x8f => ((op - 0x7f) as usize, (op - 0x7e) as usize),
+        0x90..=0x9f => ((op - 0x8e) as usize, (op - 0x8e) as usize),
+        0xa0..=0xa4 => ((op - 0x9e) as usize, 0),
+        _ => (0, 0)
+    }
+}
+
+// Instructions after which execution does not continue with the next one
+fn is_terminating(op: u8) -> bool {
+    matches!(op, STOP | RETF | JUMPF | RETURNCONTRACT | RETURN | 0xfd | 0xfe | RJUMP)
+}
+
+fn read_u16(bytes: &[u8]) -> usize {
+    ((bytes[0] as usize) << 8) | bytes[1] as usize
+}
+
+// Destinations of a relative jump, relative to the end of the instruction
+fn jump_targets(op: u8, pc: usize, immediate: &[u8]) -> Vec<isize> {
+    let next = (pc + 1 + immediate.len()) as isize;
+    let offset = |bytes: &[u8]| i16::from_be_bytes([bytes[0], bytes[1]]) as isize;
+    match op {
+        RJUMP | RJUMPI => vec![next + offset(immediate)],
+        RJUMPV => immediate[1..].chunks(2).map(|entry| next + offset(entry)).collect(),
+        _ => Vec::new()
+    }
+}
+
+struct Instruction<'a> {
+    pc: usize,
+    op: u8,
+    immediate: &'a [u8]
+}
+
+// Sections and subcontainers a code section refers to
+#[derive(Default)]
+struct References {
+    sections: BTreeSet<usize>,
+    eofcreate: BTreeSet<usize>,
+    returncontract: BTreeSet<usize>
+}
+
+impl Container {
+    pub fn parse(code: &[u8]) -> Result<Container, EofError> {
+        let mut reader = Reader { bytes: code, pos: 0 };
+        if reader.take(2) != Some(&MAGIC[..]) {
+            return Err(EofError::Header("invalid magic, expected 0xef00".to_string()));
+        }
+        let version = reader.header(1, "version")?;
+        if version != VERSION as usize {
+            return Err(EofError::Header(format!("unsupported version {}", version)));
+        }
+
+        expect_kind(&mut reader, KIND_TYPES, "types")?;
+        let types_size = reader.header(2, "types section size")?;
+        expect_kind(&mut reader, KIND_CODE, "code")?;
+        let code_sections = reader.header(2, "number of code sections")?;
+        if code_sections == 0 || code_sections > MAX_CODE_SECTIONS {
+            return Err(EofError::Header(format!("{} code sections, expected 1 to {}", code_sections, MAX_CODE_SECTIONS)));
+        }
+        if types_size != 4 * code_sections {
+            return Err(EofError::Header(format!("types section size {} does not match {} code sections", types_size, code_sections)));
+        }
+        let mut code_sizes: Vec<usize> = Vec::new();
+        for ix in 0..code_sections {
+            let size = reader.header(2, "code section size")?;
+            if size == 0 {
+                return Err(EofError::Header(format!("code section {} is empty", ix)));
+            }
+            code_sizes.push(size);
+        }
+
+        let mut container_sizes: Vec<usize> = Vec::new();
+        let mut kind = reader.header(1, "section kind")?;
+        if kind == KIND_CONTAINER as usize {
+            let containers = reader.header(2, "number of container sections")?;
+            if containers == 0 || containers > MAX_CONTAINER_SECTIONS {
+                return Err(EofError::Header(format!("{} container sections, expected 1 to {}", containers, MAX_CONTAINER_SECTIONS)));
+            }
+            for ix in 0..containers {
+                let size = reader.header(4, "container section size")?;
+                if size == 0 {
+                    return Err(EofError::Header(format!("container section {} is empty", ix)));
+                }
+                container_sizes.push(size);
+            }
+            kind = reader.header(1, "section kind")?;
+        }
+        if kind != KIND_DATA as usize {
+            return Err(EofError::Header(format!("expected data section kind 0x{:02x}, found 0x{:02x}", KIND_DATA, kind)));
+        }
+        let data_size = reader.header(2, "data section size")?;
+        if reader.header(1, "header terminator")? != TERMINATOR as usize {
+            return Err(EofError::Header("missing header terminator".to_string()));
+        }
+
+        let mut types: Vec<TypeSection> = Vec::new();
+        for _ in 0..code_sections {
+            let bytes = reader.body(4, "types section")?;
+            types.push(TypeSection { inputs: bytes[0], outputs: bytes[1], max_stack_increase: read_u16(&bytes[2..]) as u16 });
+        }
+        let mut sections: Vec<Vec<u8>> = Vec::new();
+        for (ix, size) in code_sizes.iter().enumerate() {
+            sections.push(reader.body(*size, &format!("code section {}", ix))?.to_vec());
+        }
+        let mut containers: Vec<Vec<u8>> = Vec::new();
+        for (ix, size) in container_sizes.iter().enumerate() {
+            containers.push(reader.body(*size, &format!("container section {}", ix))?.to_vec());
+        }
+        let data = &code[reader.pos..];
+        if data.len() > data_size {
+            return Err(EofError::Section(format!("{} bytes after the data section", data.len() - data_size)));
+        }
+        Ok(Container { types, code: sections, containers, data: data.to_vec(), data_size: data_size as u16 })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(KIND_TYPES);
+        bytes.extend(((4 * self.types.len()) as u16).to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend((self.code.len() as u16).to_be_bytes());
+        for code in &self.code {
+            bytes.extend((code.len() as u16).to_be_bytes());
+        }
+        if !self.containers.is_empty() {
+            bytes.push(KIND_CONTAINER);
+            bytes.extend((self.containers.len() as u16).to_be_bytes());
+            for container in &self.containers {
+                bytes.extend((container.len() as u32).to_be_bytes());
+            }
+        }
+        bytes.push(KIND_DATA);
+        bytes.extend(self.data_size.to_be_bytes());
+        bytes.push(TERMINATOR);
+        for t in &self.types {
+            bytes.extend([t.inputs, t.outputs]);
+            bytes.extend(t.max_stack_increase.to_be_bytes());
+        }
+        for code in self.code.iter().chain(self.containers.iter()) {
+            bytes.extend(code);
+        }
+        bytes.extend(&self.data);
+        bytes
+    }
+
+    // Sections of the container, code sections listed one instruction per line
+    pub fn listing(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for (section, t) in self.types.iter().enumerate() {
+            let outputs = if t.is_returning() { t.outputs.to_string() } else { "non-returning".to_string() };
+            lines.push(format!("code section {} (inputs: {}, outputs: {}, max stack increase: {})", section, t.inputs, outputs, t.max_stack_increase));
+            match self.instructions(section) {
+                Ok(instructions) => for Instruction { pc, op, immediate } in instructions {
+                    let name = opcode_name(op).unwrap_or("INVALID");
+                    match immediate.is_empty() {
+                        true => lines.push(format!("{:>8}: {}", pc, name)),
+                        false => lines.push(format!("{:>8}: {} 0x{}", pc, name, hex::encode(immediate)))
+                    }
+                },
+                Err(_) => lines.push(format!("{:>8}: 0x{}", 0, hex::encode(&self.code[section])))
+            }
+        }
+        for (index, container) in self.containers.iter().enumerate() {
+            lines.push(format!("subcontainer {}: 0x{}", index, hex::encode(container)));
+        }
+        lines.push(format!("data ({} of {} bytes): 0x{}", self.data.len(), self.data_size, hex::encode(&self.data)));
+        lines.join("\n")
+    }
+
+    // Validates the container as code deployed to an account
+    pub fn validate(&self) -> Result<(), EofError> {
+        self.validate_as(false, false)
+    }
+
+    // `initcode` containers are run by EOFCREATE, the others are deployed code. Only code
+    // deployed by RETURNCONTRACT can have a truncated data section
+    fn validate_as(&self, initcode: bool, truncated_data: bool) -> Result<(), EofError> {
+        if self.data.len() < self.data_size as usize && !truncated_data {
+            return Err(EofError::Section(format!("data section truncated, {} of {} bytes", self.data.len(), self.data_size)));
+        }
+        for (ix, t) in self.types.iter().enumerate() {
+            if ix == 0 && (t.inputs != 0 || t.is_returning()) {
+                return Err(EofError::Section("code section 0 must have 0 inputs and be non-returning".to_string()));
+            }
+            if t.inputs > MAX_SECTION_IO {
+                return Err(EofError::Section(format!("code section {} has {} inputs, the limit is {}", ix, t.inputs, MAX_SECTION_IO)));
+            }
+            if t.outputs > MAX_SECTION_IO && t.is_returning() {
+                return Err(EofError::Section(format!("code section {} has {} outputs, the limit is {}", ix, t.outputs, MAX_SECTION_IO)));
+            }
+            if t.max_stack_increase > MAX_STACK_INCREASE {
+                return Err(EofError::Section(format!("code section {} has a max stack increase of {}, the limit is {}", ix, t.max_stack_increase, MAX_STACK_INCREASE)));
+            }
+        }
+
+        let mut references: Vec<References> = Vec::new();
+        for section in 0..self.code.len() {
+            references.push(self.validate_code(section, initcode)?);
+            let height = self.max_stack_height(section, true)?;
+            let t = self.types[section];
+            if height != t.inputs as usize + t.max_stack_increase as usize {
+                return Err(EofError::Stack { section, pc: None,
+                    message: format!("max stack increase is {} but the code uses {}", t.max_stack_increase, height - t.inputs as usize) });
+            }
+        }
+
+        let mut reached: BTreeSet<usize> = BTreeSet::from([0]);
+        let mut pending: Vec<usize> = vec![0];
+        while let Some(section) = pending.pop() {
+            for target in &references[section].sections {
+                if reached.insert(*target) {
+                    pending.push(*target);
+                }
+            }
+        }
+        if let Some(section) = (0..self.code.len()).find(|s| !reached.contains(s)) {
+            return Err(EofError::Section(format!("code section {} is unreachable", section)));
+        }
+
+        for (index, code) in self.containers.iter().enumerate() {
+            let by_eofcreate = references.iter().any(|r| r.eofcreate.contains(&index));
+            let by_returncontract = references.iter().any(|r| r.returncontract.contains(&index));
+            match (by_eofcreate, by_returncontract) {
+                (true, true) => return Err(EofError::Section(format!("subcontainer {} is used by both EOFCREATE and RETURNCONTRACT", index))),
+                (false, false) => return Err(EofError::Section(format!("subcontainer {} is never referenced", index))),
+                _ => {}
+            }
+            Container::parse(code)
+                .and_then(|container| container.validate_as(by_eofcreate, by_returncontract))
+                .map_err(|error| EofError::Subcontainer { index, error: Box::new(error) })?;
+        }
+        Ok(())
+    }
+
+    // Splits a code section into instructions, checking that every opcode is valid in EOF
+    // and that immediates are complete
+    fn instructions(&self, section: usize) -> Result<Vec<Instruction<'_>>, EofError> {
+        let code = &self.code[section];
+        let mut instructions: Vec<Instruction> = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let op = code[pc];
+            let size = immediate_size(code, pc).ok_or_else(|| code_error(section, pc, format!("opcode 0x{:02x} is not valid in EOF", op)))?;
+            let immediate = code.get(pc + 1..pc + 1 + size).ok_or_else(|| code_error(section, pc, "truncated immediate"))?;
+            instructions.push(Instruction { pc, op, immediate });
+            pc += 1 + size;
+        }
+        Ok(instructions)
+    }
+
+    fn validate_code(&self, section: usize, initcode: bool) -> Result<References, EofError> {
+        let instructions = self.instructions(section)?;
+        let starts: BTreeSet<usize> = instructions.iter().map(|i| i.pc).collect();
+        let current = self.types[section];
+        let mut references = References::default();
+        let mut returns = false;
+        for Instruction { pc, op, immediate } in &instructions {
+            let (pc, op) = (*pc, *op);
+            let name = opcode_name(op).unwrap_or("");
+            for target in jump_targets(op, pc, immediate) {
+                if target < 0 || !starts.contains(&(target as usize)) {
+                    return Err(code_error(section, pc, format!("{} to {} is not the start of an instruction", name, target)));
+                }
+            }
+            match op {
+                CALLF | JUMPF => {
+                    let target = read_u16(immediate);
+                    let t = self.types.get(target).ok_or_else(|| code_error(section, pc, format!("{} to missing code section {}", name, target)))?;
+                    if op == CALLF && !t.is_returning() {
+                        return Err(code_error(section, pc, format!("CALLF to non-returning code section {}", target)));
+                    }
+                    if op == JUMPF && t.is_returning() {
+                        if !current.is_returning() || t.outputs > current.outputs {
+                            return Err(code_error(section, pc, format!("JUMPF to code section {} returning more outputs than this section", target)));
+                        }
+                        returns = true;
+                    }
+                    references.sections.insert(target);
+                },
+                RETF => {
+                    if !current.is_returning() {
+                        return Err(code_error(section, pc, "RETF in a non-returning code section"));
+                    }
+                    returns = true;
+                },
+                DATALOADN => {
+                    let offset = read_u16(immediate);
+                    if offset + 32 > self.data_size as usize {
+                        return Err(code_error(section, pc, format!("DATALOADN reads past the {} byte data section", self.data_size)));
+                    }
+                },
+                EOFCREATE | RETURNCONTRACT => {
+                    let index = immediate[0] as usize;
+                    if index >= self.containers.len() {
+                        return Err(code_error(section, pc, format!("{} of missing subcontainer {}", name, index)));
+                    }
+                    if op == EOFCREATE {
+                        references.eofcreate.insert(index);
+                    } else if initcode {
+                        references.returncontract.insert(index);
+                    } else {
+                        return Err(code_error(section, pc, "RETURNCONTRACT outside of initcode"));
+                    }
+                },
+                STOP | RETURN if initcode => return Err(code_error(section, pc, format!("{} in initcode", name))),
+                _ => {}
+            }
+        }
+        let last = &instructions[instructions.len() - 1];
+        if !is_terminating(last.op) {
+            return Err(code_error(section, last.pc, "code section does not end with a terminating instruction"));
+        }
+        if current.is_returning() && !returns {
+            return Err(EofError::Code { section, pc: None, message: "returning code section without RETF or returning JUMPF".to_string() });
+        }
+        Ok(references)
+    }
+
+    // Highest stack the code section can reach, counting its inputs. Every instruction has
+    // to be reached with a known stack height range, and backward jumps with the exact range
+    // of their target. `check_overflow` also checks the max stack increase of called sections
+    fn max_stack_height(&self, section: usize, check_overflow: bool) -> Result<usize, EofError> {
+        let instructions = self.instructions(section)?;
+        let current = self.types[section];
+        let mut heights: Vec<Option<(usize, usize)>> = vec![None; self.code[section].len()];
+        heights[0] = Some((current.inputs as usize, current.inputs as usize));
+        let mut max_height = current.inputs as usize;
+        for Instruction { pc, op, immediate } in &instructions {
+            let (pc, op) = (*pc, *op);
+            let (min, max) = heights[pc].ok_or_else(|| stack_error(section, pc, "unreachable instruction"))?;
+            let (pops, pushes) = match op {
+                CALLF | JUMPF => {
+                    let t = self.types[read_u16(immediate)];
+                    if check_overflow && max + t.max_stack_increase as usize > MAX_STACK_HEIGHT {
+                        return Err(stack_error(section, pc, "stack overflow in the called section"));
+                    }
+                    if op == JUMPF && t.is_returning() {
+                        let expected = current.outputs as usize + t.inputs as usize - t.outputs as usize;
+                        if min != max || max != expected {
+                            return Err(stack_error(section, pc, format!("JUMPF with stack height {}..{}, expected {}", min, max, expected)));
+                        }
+                    }
+                    match op {
+                        CALLF => (t.inputs as usize, t.outputs as usize),
+                        _ => (t.inputs as usize, 0)
+                    }
+                },
+                RETF => {
+                    if min != max || max != current.outputs as usize {
+                        return Err(stack_error(section, pc, format!("RETF with stack height {}..{}, expected {}", min, max, current.outputs)));
+                    }
+                    (current.outputs as usize, 0)
+                },
+                DUPN => (immediate[0] as usize + 1, immediate[0] as usize + 2),
+                SWAPN => (immediate[0] as usize + 2, immediate[0] as usize + 2),
+                EXCHANGE => {
+                    let items = (immediate[0] >> 4) as usize + (immediate[0] & 0x0f) as usize + 3;
+                    (items, items)
+                },
+                _ => stack_io(op)
+            };
+            if min < pops {
+                return Err(stack_error(section, pc, format!("stack underflow, {} items required and {} available", pops, min)));
+            }
+            let (min, max) = (min - pops + pushes, max - pops + pushes);
+            max_height = max_height.max(max);
+            if max_height > MAX_STACK_HEIGHT {
+                return Err(stack_error(section, pc, format!("stack overflow, height {}", max_height)));
+            }
+
+            let mut successors: Vec<usize> = jump_targets(op, pc, immediate).into_iter().map(|t| t as usize).collect();
+            if !is_terminating(op) {
+                successors.push(pc + 1 + immediate.len());
+            }
+            for target in successors {
+                match heights[target] {
+                    _ if target > pc => {
+                        let merged = heights[target].map(|(lo, hi)| (lo.min(min), hi.max(max))).unwrap_or((min, max));
+                        heights[target] = Some(merged);
+                    },
+                    Some(existing) if existing == (min, max) => {},
+                    _ => return Err(stack_error(section, pc, format!("backward jump to {} with stack height {}..{}, the target has {}",
+                        target, min, max, heights[target].map(|(lo, hi)| format!("{}..{}", lo, hi)).unwrap_or_else(|| "none".to_string()))))
+                }
+            }
+        }
+        Ok(max_height)
+    }
+}
+
+// Builds a container from code sections, subcontainers and data. The max stack increase of
+// every section is computed from its code
+#[derive(Debug, Clone, Default)]
+pub struct ContainerBuilder {
+    types: Vec<(u8, u8)>,
+    code: Vec<Vec<u8>>,
+    containers: Vec<Vec<u8>>,
+    data: Vec<u8>
+}
+
+impl ContainerBuilder {
+    pub fn new() -> ContainerBuilder {
+        ContainerBuilder::default()
+    }
+
+    // The first section has to take no inputs and be non-returning (NON_RETURNING outputs)
+    pub fn add_code_section(&mut self, inputs: u8, outputs: u8, code: Vec<u8>) -> &mut ContainerBuilder {
+        self.types.push((inputs, outputs));
+        self.code.push(code);
+        self
+    }
+
+    pub fn add_container(&mut self, container: Vec<u8>) -> &mut ContainerBuilder {
+        self.containers.push(container);
+        self
+    }
+
+    pub fn set_data(&mut self, data: Vec<u8>) -> &mut ContainerBuilder {
+        self.data = data;
+        self
+    }
+
+    // Encoded container, validated as code deployed to an account
+    pub fn build(&self) -> Result<Bytes, EofError> {
+        if self.code.is_empty() || self.code.len() > MAX_CODE_SECTIONS || self.containers.len() > MAX_CONTAINER_SECTIONS {
+            return Err(EofError::Section(format!("1 to {} code sections and at most {} subcontainers", MAX_CODE_SECTIONS, MAX_CONTAINER_SECTIONS)));
+        }
+        if let Some(ix) = self.code.iter().position(|c| c.is_empty() || c.len() > u16::MAX as usize) {
+            return Err(EofError::Section(format!("code section {} must have 1 to {} bytes", ix, u16::MAX)));
+        }
+        if self.data.len() > u16::MAX as usize {
+            return Err(EofError::Section(format!("data section has {} bytes, the limit is {}", self.data.len(), u16::MAX)));
+        }
+        let mut container = Container {
+            types: self.types.iter().map(|(inputs, outputs)| TypeSection { inputs: *inputs, outputs: *outputs, max_stack_increase: 0 }).collect(),
+            code: self.code.clone(),
+            containers: self.containers.clone(),
+            data: self.data.clone(),
+            data_size: self.data.len() as u16
+        };
+        for section in 0..container.code.len() {
+            container.validate_code(section, false)?;
+            let height = container.max_stack_height(section, false)?;
+            let increase = height - container.types[section].inputs as usize;
+            container.types[section].max_stack_increase = increase.min(u16::MAX as usize) as u16;
+        }
+        container.validate()?;
+        Ok(Bytes::from(container.encode()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Header of a container with a single code section of `code_size` bytes
+    fn header(code_size: u16, data_size: u16) -> Vec<u8> {
+        let mut bytes = vec![0xef, 0x00, 0x01, KIND_TYPES, 0x00, 0x04, KIND_CODE, 0x00, 0x01];
+        bytes.extend(code_size.to_be_bytes());
+        bytes.push(KIND_DATA);
+        bytes.extend(data_size.to_be_bytes());
+        bytes.push(TERMINATOR);
+        bytes
+    }
+
+    // Container with a single non-returning section
+    fn single(code: &[u8], max_stack_increase: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = header(code.len() as u16, data.len() as u16);
+        bytes.extend([0x00, NON_RETURNING]);
+        bytes.extend(max_stack_increase.to_be_bytes());
+        bytes.extend(code);
+        bytes.extend(data);
+        bytes
+    }
+
+    fn rejects_header(code: &[u8]) -> bool {
+        matches!(validate(code), Err(EofError::Header(_)))
+    }
+
+    fn rejects_section(code: &[u8]) -> bool {
+        matches!(validate(code), Err(EofError::Section(_)))
+    }
+
+    fn rejects_code(code: &[u8]) -> bool {
+        matches!(validate(code), Err(EofError::Code { .. }))
+    }
+
+    fn rejects_stack(code: &[u8]) -> bool {
+        matches!(validate(code), Err(EofError::Stack { .. }))
+    }
+
+    #[test]
+    fn accepts_minimal_container() {
+        let code = single(&[STOP], 0, &[]);
+        let container = validate(&code).unwrap();
+        assert_eq!(container.code, vec![vec![STOP]]);
+        assert_eq!(container.encode(), code);
+        assert!(is_eof(&code));
+        assert!(!is_eof(&[0x60, 0x00]));
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let mut bad_magic = single(&[STOP], 0, &[]);
+        bad_magic[1] = 0x01;
+        assert!(rejects_header(&bad_magic));
+        let mut bad_version = single(&[STOP], 0, &[]);
+        bad_version[2] = 0x02;
+        assert!(rejects_header(&bad_version));
+        let mut types_size = single(&[STOP], 0, &[]);
+        types_size[5] = 0x08;
+        assert!(rejects_header(&types_size));
+        let mut no_terminator = single(&[STOP], 0, &[]);
+        no_terminator[14] = 0x01;
+        assert!(rejects_header(&no_terminator));
+        assert!(rejects_header(&header(1, 0)[..8]));
+        // No code sections
+        assert!(rejects_header(&[0xef, 0x00, 0x01, KIND_TYPES, 0x00, 0x00, KIND_CODE, 0x00, 0x00, KIND_DATA, 0x00, 0x00, TERMINATOR]));
+    }
+
+    #[test]
+    fn rejects_invalid_sections() {
+        let mut trailing = single(&[STOP], 0, &[]);
+        trailing.push(0x00);
+        assert!(rejects_section(&trailing));
+        let mut truncated_data = header(1, 2);
+        truncated_data.extend([0x00, NON_RETURNING, 0x00, 0x00, STOP, 0xaa]);
+        assert!(rejects_section(&truncated_data));
+        // The first section must be non-returning
+        let mut returning = header(1, 0);
+        returning.extend([0x00, 0x00, 0x00, 0x00, RETF]);
+        assert!(rejects_section(&returning));
+        let unreachable = Container {
+            types: vec![TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_increase: 0 }; 2],
+            code: vec![vec![STOP], vec![STOP]],
+            containers: Vec::new(),
+            data: Vec::new(),
+            data_size: 0
+        };
+        assert!(matches!(unreachable.validate(), Err(EofError::Section(m)) if m.contains("unreachable")));
+    }
+
+    #[test]
+    fn rejects_invalid_code() {
+        // Legacy JUMP
+        assert!(rejects_code(&single(&[0x5f, 0x56], 1, &[])));
+        // PUSH1 without its immediate
+        assert!(rejects_code(&single(&[0x60], 1, &[])));
+        // Falls off the end of the section
+        assert!(rejects_code(&single(&[0x5f], 1, &[])));
+        // RJUMP into the immediate of the PUSH1
+        assert!(rejects_code(&single(&[0x60, 0x00, RJUMP, 0xff, 0xfc], 1, &[])));
+        assert!(rejects_code(&single(&[RETF], 0, &[])));
+        assert!(rejects_code(&single(&[DATALOADN, 0x00, 0x00, 0x50, STOP], 1, &[0; 31])));
+        assert!(validate(&single(&[DATALOADN, 0x00, 0x00, 0x50, STOP], 1, &[0; 32])).is_ok());
+        assert!(rejects_code(&single(&[0x5f, 0x5f, RETURNCONTRACT, 0x00], 2, &[])));
+    }
+
+    #[test]
+    fn rejects_invalid_stack_use() {
+        // ADD on an empty stack
+        assert!(rejects_stack(&single(&[0x01, STOP], 0, &[])));
+        // Declared max stack increase does not match the code
+        assert!(rejects_stack(&single(&[0x5f, STOP], 0, &[])));
+        // Loop that grows the stack on every iteration
+        assert!(rejects_stack(&single(&[0x5f, RJUMP, 0xff, 0xfc], 1, &[])));
+        assert!(validate(&single(&[0x5f, 0x50, RJUMP, 0xff, 0xfb], 1, &[])).is_ok());
+    }
+
+    #[test]
+    fn builder_round_trip() {
+        // Section 0 adds two values with section 1
+        let code = ContainerBuilder::new()
+            .add_code_section(0, NON_RETURNING, vec![0x5f, 0x5f, CALLF, 0x00, 0x01, 0x50, STOP])
+            .add_code_section(2, 1, vec![0x01, RETF])
+            .set_data(vec![1, 2, 3])
+            .build()
+            .unwrap();
+        let container = validate(&code.0).unwrap();
+        assert_eq!(container.types.iter().map(|t| t.max_stack_increase).collect::<Vec<u16>>(), vec![2, 0]);
+        assert_eq!(container.code[1], vec![0x01, RETF]);
+        assert_eq!(container.data, vec![1, 2, 3]);
+        assert_eq!(container.encode(), code.0);
+    }
+
+    #[test]
+    fn builder_with_subcontainers() {
+        let deployed = single(&[STOP], 0, &[]);
+        let initcode = Container {
+            types: vec![TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_increase: 2 }],
+            code: vec![vec![0x5f, 0x5f, RETURNCONTRACT, 0x00]],
+            containers: vec![deployed],
+            data: Vec::new(),
+            data_size: 0
+        }.encode();
+        let eofcreate = vec![0x5f, 0x5f, 0x5f, 0x5f, EOFCREATE, 0x00, 0x50, STOP];
+
+        let code = ContainerBuilder::new().add_code_section(0, NON_RETURNING, eofcreate.clone()).add_container(initcode.clone()).build().unwrap();
+        assert_eq!(validate(&code.0).unwrap().containers, vec![initcode.clone()]);
+
+        // Deployed code run by EOFCREATE is not valid initcode
+        let invalid = ContainerBuilder::new().add_code_section(0, NON_RETURNING, eofcreate.clone()).add_container(single(&[STOP], 0, &[])).build();
+        assert!(matches!(invalid, Err(EofError::Subcontainer { index: 0, .. })));
+        let unused = ContainerBuilder::new().add_code_section(0, NON_RETURNING, vec![STOP]).add_container(initcode).build();
+        assert!(matches!(unused, Err(EofError::Section(_))));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_sections() {
+        assert!(ContainerBuilder::new().build().is_err());
+        assert!(ContainerBuilder::new().add_code_section(0, NON_RETURNING, Vec::new()).build().is_err());
+        assert!(ContainerBuilder::new().add_code_section(0, 0, vec![RETF]).build().is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing `path` failed
+    Io { path: String, source: io::Error },
+    // `path` is not valid JSON or does not have the expected layout
+    Json { path: String, line: usize, column: usize, message: String },
+    // The configured t8n binary cannot be executed
+    T8nNotFound { path: String, source: io::Error },
+    // The t8n tool ran but exited with a non-zero status
+    T8nExit { code: Option<i32>, stderr: String },
+    // A state or blockchain test is missing data or references something that does not exist
+    MalformedFixture(String),
+    // A command argument or a context value is not valid
+    InvalidInput(String)
+}
+
+impl Error {
+    pub fn io(path: &str, source: io::Error) -> Error {
+        Error::Io { path: path.to_string(), source }
+    }
+
+    pub fn json(path: &str, source: serde_json::Error) -> Error {
+        Error::Json { path: path.to_string(), line: source.line(), column: source.column(), message: source.to_string() }
+    }
+
+    pub fn fixture(message: impl Into<String>) -> Error {
+        Error::MalformedFixture(message.into())
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Error {
+        Error::InvalidInput(message.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Json { path, line, column, message } => write!(f, "{}:{}:{}: {}", path, line, column, message),
+            Error::T8nNotFound { path, source } => write!(f, "cannot execute t8n tool `{}`: {}", path, source),
+            Error::T8nExit { code, stderr } => {
+                match code {
+                    Some(code) => write!(f, "t8n tool exited with status {}", code)?,
+                    None => write!(f, "t8n tool was terminated by a signal")?
+                }
+                if !stderr.trim().is_empty() {
+                    write!(f, "\n{}", stderr.trim_end())?;
+                }
+                Ok(())
+            },
+            Error::MalformedFixture(message) => write!(f, "malformed fixture: {}", message),
+            Error::InvalidInput(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::T8nNotFound { source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
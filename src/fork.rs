@@ -0,0 +1,291 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Error;
+
+// Forks known by t8n, in activation order. A transition fork sits right after the fork it
+// starts from: it follows those rules until the transition block (or timestamp) is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum HardFork {
+    Frontier,
+    FrontierToHomesteadAt5,
+    Homestead,
+    HomesteadToDaoAt5,
+    HomesteadToEIP150At5,
+    EIP150,
+    EIP158,
+    EIP158ToByzantiumAt5,
+    Byzantium,
+    ByzantiumToConstantinopleAt5,
+    ByzantiumToConstantinopleFixAt5,
+    Constantinople,
+    ConstantinopleFix,
+    ConstantinopleFixToIstanbulAt5,
+    Istanbul,
+    MuirGlacier,
+    Berlin,
+    BerlinToLondonAt5,
+    London,
+    ArrowGlacier,
+    ArrowGlacierToParisAtDiffC0000,
+    GrayGlacier,
+    #[default]
+    Merge,
+    ParisToShanghaiAtTime15k,
+    Shanghai,
+    ShanghaiToCancunAtTime15k,
+    Cancun,
+    CancunToPragueAtTime15k,
+    Prague,
+    PragueToOsakaAtTime15k,
+    Osaka
+}
+
+impl HardFork {
+    pub const ALL: [HardFork; 31] = [
+        HardFork::Frontier, HardFork::FrontierToHomesteadAt5, HardFork::Homestead, HardFork::HomesteadToDaoAt5,
+        HardFork::HomesteadToEIP150At5, HardFork::EIP150, HardFork::EIP158, HardFork::EIP158ToByzantiumAt5,
+        HardFork::Byzantium, HardFork::ByzantiumToConstantinopleAt5, HardFork::ByzantiumToConstantinopleFixAt5,
+        HardFork::Constantinople, HardFork::ConstantinopleFix, HardFork::ConstantinopleFixToIstanbulAt5,
+        HardFork::Istanbul, HardFork::MuirGlacier, HardFork::Berlin, HardFork::BerlinToLondonAt5, HardFork::London,
+        HardFork::ArrowGlacier, HardFork::ArrowGlacierToParisAtDiffC0000, HardFork::GrayGlacier, HardFork::Merge,
+        HardFork::ParisToShanghaiAtTime15k, HardFork::Shanghai, HardFork::ShanghaiToCancunAtTime15k, HardFork::Cancun,
+        HardFork::CancunToPragueAtTime15k, HardFork::Prague, HardFork::PragueToOsakaAtTime15k, HardFork::Osaka
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HardFork::Frontier => "Frontier",
+            HardFork::FrontierToHomesteadAt5 => "FrontierToHomesteadAt5",
+            HardFork::Homestead => "Homestead",
+            HardFork::HomesteadToDaoAt5 => "HomesteadToDaoAt5",
+            HardFork::HomesteadToEIP150At5 => "HomesteadToEIP150At5",
+            HardFork::EIP150 => "EIP150",
+            HardFork::EIP158 => "EIP158",
+            HardFork::EIP158ToByzantiumAt5 => "EIP158ToByzantiumAt5",
+            HardFork::Byzantium => "Byzantium",
+            HardFork::ByzantiumToConstantinopleAt5 => "ByzantiumToConstantinopleAt5",
+            HardFork::ByzantiumToConstantinopleFixAt5 => "ByzantiumToConstantinopleFixAt5",
+            HardFork::Constantinople => "Constantinople",
+            HardFork::ConstantinopleFix => "ConstantinopleFix",
+            HardFork::ConstantinopleFixToIstanbulAt5 => "ConstantinopleFixToIstanbulAt5",
+            HardFork::Istanbul => "Istanbul",
+            HardFork::MuirGlacier => "MuirGlacier",
+            HardFork::Berlin => "Berlin",
+            HardFork::BerlinToLondonAt5 => "BerlinToLondonAt5",
+            HardFork::London => "London",
+            HardFork::ArrowGlacier => "ArrowGlacier",
+            HardFork::ArrowGlacierToParisAtDiffC0000 => "ArrowGlacierToParisAtDiffC0000",
+            HardFork::GrayGlacier => "GrayGlacier",
+            HardFork::Merge => "Merge",
+            HardFork::ParisToShanghaiAtTime15k => "ParisToShanghaiAtTime15k",
+            HardFork::Shanghai => "Shanghai",
+            HardFork::ShanghaiToCancunAtTime15k => "ShanghaiToCancunAtTime15k",
+            HardFork::Cancun => "Cancun",
+            HardFork::CancunToPragueAtTime15k => "CancunToPragueAtTime15k",
+            HardFork::Prague => "Prague",
+            HardFork::PragueToOsakaAtTime15k => "PragueToOsakaAtTime15k",
+            HardFork::Osaka => "Osaka"
+        }
+    }
+
+    // Other names used by fixtures and older t8n versions
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            HardFork::ConstantinopleFix => &["Petersburg"],
+            HardFork::ArrowGlacierToParisAtDiffC0000 => &["ArrowGlacierToMergeAtDiffC0000"],
+            HardFork::Merge => &["Paris"],
+            HardFork::ParisToShanghaiAtTime15k => &["MergeToShanghaiAtTime15k"],
+            _ => &[]
+        }
+    }
+
+    // For transition forks, the forks before and after the transition
+    pub fn transition(&self) -> Option<(HardFork, HardFork)> {
+        match self {
+            HardFork::FrontierToHomesteadAt5 => Some((HardFork::Frontier, HardFork::Homestead)),
+            HardFork::HomesteadToDaoAt5 => Some((HardFork::Homestead, HardFork::Homestead)),
+            HardFork::HomesteadToEIP150At5 => Some((HardFork::Homestead, HardFork::EIP150)),
+            HardFork::EIP158ToByzantiumAt5 => Some((HardFork::EIP158, HardFork::Byzantium)),
+            HardFork::ByzantiumToConstantinopleAt5 => Some((HardFork::Byzantium, HardFork::Constantinople)),
+            HardFork::ByzantiumToConstantinopleFixAt5 => Some((HardFork::Byzantium, HardFork::ConstantinopleFix)),
+            HardFork::ConstantinopleFixToIstanbulAt5 => Some((HardFork::ConstantinopleFix, HardFork::Istanbul)),
+            HardFork::BerlinToLondonAt5 => Some((HardFork::Berlin, HardFork::London)),
+            HardFork::ArrowGlacierToParisAtDiffC0000 => Some((HardFork::ArrowGlacier, HardFork::Merge)),
+            HardFork::ParisToShanghaiAtTime15k => Some((HardFork::Merge, HardFork::Shanghai)),
+            HardFork::ShanghaiToCancunAtTime15k => Some((HardFork::Shanghai, HardFork::Cancun)),
+            HardFork::CancunToPragueAtTime15k => Some((HardFork::Cancun, HardFork::Prague)),
+            HardFork::PragueToOsakaAtTime15k => Some((HardFork::Prague, HardFork::Osaka)),
+            _ => None
+        }
+    }
+
+    // Fork whose rules apply to a block. The difficulty transition is taken at the first
+    // block with zero difficulty, since the total difficulty is not known here
+    pub fn active(&self, number: u64, timestamp: u64, difficulty_is_zero: bool) -> HardFork {
+        let (before, after) = match self.transition() {
+            Some(transition) => transition,
+            None => return *self
+        };
+        let transitioned = match self {
+            HardFork::ArrowGlacierToParisAtDiffC0000 => difficulty_is_zero,
+            HardFork::ParisToShanghaiAtTime15k | HardFork::ShanghaiToCancunAtTime15k
+                | HardFork::CancunToPragueAtTime15k | HardFork::PragueToOsakaAtTime15k => timestamp >= 15_000,
+            _ => number >= 5
+        };
+        if transitioned { after } else { before }
+    }
+
+    // Fork rules before any transition, used for the environment defaults
+    pub fn base(&self) -> HardFork {
+        self.transition().map(|(before, _)| before).unwrap_or(*self)
+    }
+
+    // Spurious Dragon, replay protected (EIP-155) transactions
+    pub fn is_eip158(&self) -> bool {
+        self.base() >= HardFork::EIP158
+    }
+
+    pub fn is_london(&self) -> bool {
+        self.base() >= HardFork::London
+    }
+
+    pub fn is_merge(&self) -> bool {
+        self.base() >= HardFork::Merge
+    }
+
+    pub fn is_shanghai(&self) -> bool {
+        self.base() >= HardFork::Shanghai
+    }
+
+    pub fn is_cancun(&self) -> bool {
+        self.base() >= HardFork::Cancun
+    }
+
+    pub fn is_prague(&self) -> bool {
+        self.base() >= HardFork::Prague
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let substitution = previous + usize::from(ca != b[j]);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl FromStr for HardFork {
+    type Err = Error;
+
+    // Names are matched ignoring case, unknown names get the closest known ones as suggestion
+    fn from_str(s: &str) -> Result<HardFork, Error> {
+        let s = s.trim();
+        let names = |fork: &HardFork| std::iter::once(fork.name()).chain(fork.aliases().iter().copied()).collect::<Vec<&str>>();
+        if let Some(fork) = HardFork::ALL.iter().find(|f| names(f).iter().any(|n| n.eq_ignore_ascii_case(s))) {
+            return Ok(*fork);
+        }
+
+        let lower = s.to_lowercase();
+        let mut suggestions: Vec<(usize, &str)> = HardFork::ALL.iter()
+            .flat_map(names)
+            .map(|n| (edit_distance(&lower, &n.to_lowercase()), n))
+            .filter(|(distance, _)| *distance <= 3)
+            .collect();
+        suggestions.sort();
+        let suggestions: Vec<&str> = suggestions.into_iter().take(3).map(|(_, n)| n).collect();
+        if suggestions.is_empty() {
+            let known: Vec<&str> = HardFork::ALL.iter().map(|f| f.name()).collect();
+            return Err(Error::invalid(format!("Unknown hard fork `{}`, known forks: {}", s, known.join(", "))));
+        }
+        Err(Error::invalid(format!("Unknown hard fork `{}`, did you mean {}?", s, suggestions.join(" or "))))
+    }
+}
+
+impl fmt::Display for HardFork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Serialize for HardFork {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for HardFork {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<HardFork, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(name: &str) -> String {
+        name.parse::<HardFork>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_names_and_aliases() {
+        for fork in HardFork::ALL {
+            assert_eq!(fork.name().parse::<HardFork>().unwrap(), fork);
+            assert_eq!(fork.to_string().to_uppercase().parse::<HardFork>().unwrap(), fork);
+        }
+        assert_eq!("Paris".parse::<HardFork>().unwrap(), HardFork::Merge);
+        assert_eq!(" paris ".parse::<HardFork>().unwrap(), HardFork::Merge);
+        assert_eq!("Petersburg".parse::<HardFork>().unwrap(), HardFork::ConstantinopleFix);
+        assert_eq!("ArrowGlacierToMergeAtDiffC0000".parse::<HardFork>().unwrap(), HardFork::ArrowGlacierToParisAtDiffC0000);
+        assert_eq!("MergeToShanghaiAtTime15k".parse::<HardFork>().unwrap(), HardFork::ParisToShanghaiAtTime15k);
+        assert_eq!(serde_json::from_str::<HardFork>("\"paris\"").unwrap(), HardFork::Merge);
+        assert_eq!(serde_json::to_string(&HardFork::ConstantinopleFix).unwrap(), "\"ConstantinopleFix\"");
+    }
+
+    #[test]
+    fn suggests_close_names() {
+        assert_eq!(error("Shangai"), "Unknown hard fork `Shangai`, did you mean Shanghai?");
+        assert_eq!(error("Pari"), "Unknown hard fork `Pari`, did you mean Paris?");
+        assert!(error("Londn").contains("did you mean London"));
+        let unknown = error("Amsterdam");
+        assert!(unknown.starts_with("Unknown hard fork `Amsterdam`, known forks: Frontier, "), "{}", unknown);
+        assert!(unknown.ends_with("Osaka"));
+        assert!(serde_json::from_str::<HardFork>("\"Shangai\"").is_err());
+    }
+
+    #[test]
+    fn activates_transitions() {
+        let fork = HardFork::BerlinToLondonAt5;
+        assert_eq!(fork.active(4, 0, false), HardFork::Berlin);
+        assert_eq!(fork.active(5, 0, false), HardFork::London);
+        assert_eq!(fork.base(), HardFork::Berlin);
+        assert!(!fork.is_london());
+
+        let fork = HardFork::ShanghaiToCancunAtTime15k;
+        assert_eq!(fork.active(100, 14_999, true), HardFork::Shanghai);
+        assert_eq!(fork.active(1, 15_000, true), HardFork::Cancun);
+
+        let fork = HardFork::ArrowGlacierToParisAtDiffC0000;
+        assert_eq!(fork.active(100, 0, false), HardFork::ArrowGlacier);
+        assert_eq!(fork.active(1, 0, true), HardFork::Merge);
+
+        assert_eq!(HardFork::HomesteadToDaoAt5.active(5, 0, false), HardFork::Homestead);
+        assert_eq!(HardFork::Cancun.active(0, 0, false), HardFork::Cancun);
+        assert_eq!(HardFork::Cancun.base(), HardFork::Cancun);
+        for fork in HardFork::ALL {
+            if let Some((before, after)) = fork.transition() {
+                assert!(before < fork && fork < after || after == before, "{}", fork);
+            }
+        }
+    }
+}
@@ -1,6 +1,21 @@
-mod config;
+pub mod asm;
+pub mod batch;
+pub mod blockchain_test;
+pub mod cfg;
+pub mod completion;
+pub mod config;
 pub mod context;
+pub mod crypto;
+pub mod diff;
+pub mod disasm;
+pub mod eof;
+pub mod error;
+pub mod fork;
+pub mod opcode;
 pub mod repl;
+pub mod rlp;
+pub mod trace_diff;
+pub mod types;
 
 mod t8n {
 }
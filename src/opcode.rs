@@ -0,0 +1,107 @@
+use crate::fork::HardFork;
+
+// EVM opcodes known by the assembler and the disassembler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    pub code: u8,
+    pub name: &'static str,
+    // Bytes of immediate data following the opcode
+    pub immediate: usize
+}
+
+const fn op(code: u8, name: &'static str, immediate: usize) -> Opcode {
+    Opcode { code, name, immediate }
+}
+
+pub const OPCODES: [Opcode; 150] = [
+    op(0x00, "STOP", 0), op(0x01, "ADD", 0), op(0x02, "MUL", 0), op(0x03, "SUB", 0),
+    op(0x04, "DIV", 0), op(0x05, "SDIV", 0), op(0x06, "MOD", 0), op(0x07, "SMOD", 0),
+    op(0x08, "ADDMOD", 0), op(0x09, "MULMOD", 0), op(0x0a, "EXP", 0), op(0x0b, "SIGNEXTEND", 0),
+    op(0x10, "LT", 0), op(0x11, "GT", 0), op(0x12, "SLT", 0), op(0x13, "SGT", 0),
+    op(0x14, "EQ", 0), op(0x15, "ISZERO", 0), op(0x16, "AND", 0), op(0x17, "OR", 0),
+    op(0x18, "XOR", 0), op(0x19, "NOT", 0), op(0x1a, "BYTE", 0), op(0x1b, "SHL", 0),
+    op(0x1c, "SHR", 0), op(0x1d, "SAR", 0), op(0x1e, "CLZ", 0), op(0x20, "KECCAK256", 0),
+    op(0x30, "ADDRESS", 0), op(0x31, "BALANCE", 0), op(0x32, "ORIGIN", 0), op(0x33, "CALLER", 0),
+    op(0x34, "CALLVALUE", 0), op(0x35, "CALLDATALOAD", 0), op(0x36, "CALLDATASIZE", 0), op(0x37, "CALLDATACOPY", 0),
+    op(0x38, "CODESIZE", 0), op(0x39, "CODECOPY", 0), op(0x3a, "GASPRICE", 0), op(0x3b, "EXTCODESIZE", 0),
+    op(0x3c, "EXTCODECOPY", 0), op(0x3d, "RETURNDATASIZE", 0), op(0x3e, "RETURNDATACOPY", 0), op(0x3f, "EXTCODEHASH", 0),
+    op(0x40, "BLOCKHASH", 0), op(0x41, "COINBASE", 0), op(0x42, "TIMESTAMP", 0), op(0x43, "NUMBER", 0),
+    op(0x44, "PREVRANDAO", 0), op(0x45, "GASLIMIT", 0), op(0x46, "CHAINID", 0), op(0x47, "SELFBALANCE", 0),
+    op(0x48, "BASEFEE", 0), op(0x49, "BLOBHASH", 0), op(0x4a, "BLOBBASEFEE", 0), op(0x50, "POP", 0),
+    op(0x51, "MLOAD", 0), op(0x52, "MSTORE", 0), op(0x53, "MSTORE8", 0), op(0x54, "SLOAD", 0),
+    op(0x55, "SSTORE", 0), op(0x56, "JUMP", 0), op(0x57, "JUMPI", 0), op(0x58, "PC", 0),
+    op(0x59, "MSIZE", 0), op(0x5a, "GAS", 0), op(0x5b, "JUMPDEST", 0), op(0x5c, "TLOAD", 0),
+    op(0x5d, "TSTORE", 0), op(0x5e, "MCOPY", 0), op(0x5f, "PUSH0", 0), op(0x60, "PUSH1", 1),
+    op(0x61, "PUSH2", 2), op(0x62, "PUSH3", 3), op(0x63, "PUSH4", 4), op(0x64, "PUSH5", 5),
+    op(0x65, "PUSH6", 6), op(0x66, "PUSH7", 7), op(0x67, "PUSH8", 8), op(0x68, "PUSH9", 9),
+    op(0x69, "PUSH10", 10), op(0x6a, "PUSH11", 11), op(0x6b, "PUSH12", 12), op(0x6c, "PUSH13", 13),
+    op(0x6d, "PUSH14", 14), op(0x6e, "PUSH15", 15), op(0x6f, "PUSH16", 16), op(0x70, "PUSH17", 17),
+    op(0x71, "PUSH18", 18), op(0x72, "PUSH19", 19), op(0x73, "PUSH20", 20), op(0x74, "PUSH21", 21),
+    op(0x75, "PUSH22", 22), op(0x76, "PUSH23", 23), op(0x77, "PUSH24", 24), op(0x78, "PUSH25", 25),
+    op(0x79, "PUSH26", 26), op(0x7a, "PUSH27", 27), op(0x7b, "PUSH28", 28), op(0x7c, "PUSH29", 29),
+    op(0x7d, "PUSH30", 30), op(0x7e, "PUSH31", 31), op(0x7f, "PUSH32", 32), op(0x80, "DUP1", 0),
+    op(0x81, "DUP2", 0), op(0x82, "DUP3", 0), op(0x83, "DUP4", 0), op(0x84, "DUP5", 0),
+    op(0x85, "DUP6", 0), op(0x86, "DUP7", 0), op(0x87, "DUP8", 0), op(0x88, "DUP9", 0),
+    op(0x89, "DUP10", 0), op(0x8a, "DUP11", 0), op(0x8b, "DUP12", 0), op(0x8c, "DUP13", 0),
+    op(0x8d, "DUP14", 0), op(0x8e, "DUP15", 0), op(0x8f, "DUP16", 0), op(0x90, "SWAP1", 0),
+    op(0x91, "SWAP2", 0), op(0x92, "SWAP3", 0), op(0x93, "SWAP4", 0), op(0x94, "SWAP5", 0),
+    op(0x95, "SWAP6", 0), op(0x96, "SWAP7", 0), op(0x97, "SWAP8", 0), op(0x98, "SWAP9", 0),
+    op(0x99, "SWAP10", 0), op(0x9a, "SWAP11", 0), op(0x9b, "SWAP12", 0), op(0x9c, "SWAP13", 0),
+    op(0x9d, "SWAP14", 0), op(0x9e, "SWAP15", 0), op(0x9f, "SWAP16", 0), op(0xa0, "LOG0", 0),
+    op(0xa1, "LOG1", 0), op(0xa2, "LOG2", 0), op(0xa3, "LOG3", 0), op(0xa4, "LOG4", 0),
+    op(0xf0, "CREATE", 0), op(0xf1, "CALL", 0), op(0xf2, "CALLCODE", 0), op(0xf3, "RETURN", 0),
+    op(0xf4, "DELEGATECALL", 0), op(0xf5, "CREATE2", 0), op(0xfa, "STATICCALL", 0), op(0xfd, "REVERT", 0),
+    op(0xfe, "INVALID", 0), op(0xff, "SELFDESTRUCT", 0)
+];
+
+// Names used by older tools and fixtures
+const ALIASES: [(&str, u8); 2] = [("SHA3", 0x20), ("DIFFICULTY", 0x44)];
+
+impl Opcode {
+    pub fn from_byte(code: u8) -> Option<Opcode> {
+        OPCODES.iter().find(|o| o.code == code).copied()
+    }
+
+    // Mnemonics are matched ignoring case
+    pub fn from_name(name: &str) -> Option<Opcode> {
+        OPCODES.iter().find(|o| o.name.eq_ignore_ascii_case(name)).copied()
+            .or_else(|| ALIASES.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)).and_then(|(_, code)| Opcode::from_byte(*code)))
+    }
+
+    pub fn push(size: usize) -> Option<Opcode> {
+        match size {
+            0..=32 => Opcode::from_byte(0x5f + size as u8),
+            _ => None
+        }
+    }
+
+    pub fn is_push(&self) -> bool {
+        (0x5f..=0x7f).contains(&self.code)
+    }
+}
+
+impl Opcode {
+    // First fork where the opcode is valid
+    pub fn introduced(&self) -> HardFork {
+        match self.code {
+            0xf4 => HardFork::Homestead,
+            0x3d | 0x3e | 0xfa | 0xfd => HardFork::Byzantium,
+            0x1b | 0x1c | 0x1d | 0x3f | 0xf5 => HardFork::Constantinople,
+            0x46 | 0x47 => HardFork::Istanbul,
+            0x48 => HardFork::London,
+            0x5f => HardFork::Shanghai,
+            0x49 | 0x4a | 0x5c | 0x5d | 0x5e => HardFork::Cancun,
+            0x1e => HardFork::Osaka,
+            _ => HardFork::Frontier
+        }
+    }
+
+    // Opcode of `code` under the rules of `fork`, None if it is not defined there
+    pub fn at_fork(code: u8, fork: HardFork) -> Option<Opcode> {
+        let opcode = Opcode::from_byte(code).filter(|o| fork.base() >= o.introduced())?;
+        if code == 0x44 && !fork.is_merge() {
+            return Some(Opcode { name: "DIFFICULTY", ..opcode });
+        }
+        Some(opcode)
+    }
+}
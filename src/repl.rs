@@ -1,40 +1,71 @@
-use std::io;
-use std::process::Command;
-use std::io::Write;
 use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::Editor;
+
+use crate::asm;
+use crate::completion::ReplHelper;
+use crate::config;
+use crate::context::{Alloc, Context, Indexes, Trace, TransactionT8n};
+use crate::crypto;
+use crate::eof::{self, ContainerBuilder};
+use crate::error::Error;
+use crate::fork::HardFork;
+use crate::trace_diff;
+use crate::types::{Address, Bytes, B256, U256};
 
-use crate::context::{Alloc, Context, TransactionT8n};
+// Commands understood by the REPL
+pub const COMMANDS: &[&str] = &["exit", "help", "extract", "dir", "alloc", "alloc.add", "alloc.add.default", "alloc.add.random", "addcode", "addeof", "code", "cfg", "env", "env.set.difficulty", "env.set.currentrandom", "env.set.excessblobgas", "env.set.parentblobgas", "env.set.beaconroot", "env.set.parentgas", "env.set.parentdifficulty", "env.next", "env.add.withdrawal", "env.remove.withdrawal", "env.set.blockhash", "env.add.ommer", "env.remove.ommer", "txs", "tx.new", "tx.set.sender", "tx.set.receiver", "tx.set.input", "tx.set.value", "tx.set.type", "tx.set.gasprice", "tx.set.maxfee", "tx.set.priorityfee", "tx.add.accesslist", "tx.set.blobfee", "tx.add.blobhash", "tx.add.authorization", "tx.sign", "hf", "run", "diff-run", "tracediff", "save", "load", "t8n", "evm"];
 
 struct ReplCommand {
     command_name: String,
     command_params: Vec<String>,
 }
 
+// Reports whether EOF code is a valid container, legacy code is not checked
+fn print_eof_status(account: &Alloc) {
+    match account.eof() {
+        Some(Ok(container)) => println!("Valid EOF container: {} code sections, {} subcontainers, {} data bytes",
+            container.code.len(), container.containers.len(), container.data.len()),
+        Some(Err(e)) => println!("Warning: invalid EOF container, {}", e),
+        None => {}
+    }
+}
+
+fn parse_u8(s: &str) -> Option<u8> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok()
+    }
+}
+
+// Code section of addeof with its inputs and outputs, `<inputs>:<outputs>` is optional
+fn parse_code_section(section: &str) -> Result<(u8, u8, Bytes), Error> {
+    let (first, rest) = section.split_once(char::is_whitespace).unwrap_or((section, ""));
+    let io = first.split_once(':').and_then(|(inputs, outputs)| Some((parse_u8(inputs)?, parse_u8(outputs)?)));
+    match io {
+        Some((inputs, outputs)) => Ok((inputs, outputs, asm::parse_code(rest)?)),
+        None => Ok((0, eof::NON_RETURNING, asm::parse_code(section)?))
+    }
+}
+
 impl ReplCommand {
     pub fn from_string(st : String) -> Option<ReplCommand> {
-        let valid_commands = vec!["exit", "help", "extract", "dir", "alloc", "alloc.add", "alloc.add.default", "addcode", "env", "env.set.difficulty", "env.set.currentrandom", "txs", "tx.new", "tx.set.sender", "tx.set.receiver", "tx.set.input", "tx.set.value", "hf", "run", "save", "load", "t8n", "evm"];
 
-        let input_command = st.clone();
-        let input_command = input_command.trim();
-        let words: Vec<&str> = input_command.split(' ').collect();
+        let input_command = st.trim();
+        let mut words = input_command.split_whitespace();
 
-        let cmd : String;
-        let mut params: Vec<String> = Vec::new();
-        if words.len() == 0 {
-            cmd = st.trim().to_string(); 
-        } else {
-            cmd = words[0].trim().to_string();
-            if words.len() > 1 {
-                for w in words.iter().skip(1) {
-                    params.push(w.to_string());
-                }
-            }
-        }
+        let cmd = words.next().unwrap_or("").to_string();
+        let params: Vec<String> = words.map(|w| w.to_string()).collect();
 
-        if valid_commands.iter().find(|&&s| s == cmd.as_str()).is_some() {
+        if COMMANDS.contains(&cmd.as_str()) {
            return Some(ReplCommand { command_name : cmd, command_params: params });
         }
-        return None;
+        None
     }
 
     fn check_params(&self, total: usize, param_names: &str) -> Result<(), ()>{
@@ -42,60 +73,160 @@ impl ReplCommand {
             println!("Error: Expected {} parameters ({}).", total, param_names);
             return Err(())
         }
-        return Ok(())
+        Ok(())
+    }
+
+    // Parses the transaction index in parameter `param`, checking that the transaction exists
+    fn tx_index(&self, param: usize, ctx: &Context) -> Option<usize> {
+        match self.command_params[param].parse::<usize>() {
+            Ok(index) if index < ctx.txs.len() => Some(index),
+            Ok(_) => {
+                println!("Transaction not found!");
+                None
+            },
+            Err(_) => {
+                println!("Index {} is not valid!", self.command_params[param]);
+                None
+            }
+        }
+    }
+
+    // Parses parameter `param` as an address, hash or quantity, printing why it is not valid
+    fn typed_param<T: FromStr<Err = Error>>(&self, param: usize) -> Option<T> {
+        match self.command_params[param].parse::<T>() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                println!("Error: {}", e);
+                None
+            }
+        }
     }
 
     fn cmd_help(&self) {
         println!("\thelp\t\t\tShows this help");
         println!("\tdir <path>\t\tSets <path> as the current working directory");
-        println!("\textract <test>\t\tExtract context information from Ethereum State Test");
+        println!("\textract <file> [test] [data,gas,value]\tExtract a test case from Ethereum State Test");
         println!("\thf <hf_name>\t\tSet HardFork");
         println!("\tt8n <t8n path>\t\tSet t8n tool path");
         println!("\tevm <evm_path>\t\tSet custom EVMC vm");
         println!("\talloc\t\t\tShow current allocation data");
-        println!("\talloc.add [address]\tCreates new account");
+        println!("\talloc.add [address|secret key]\tCreates new account, funded when given a secret key");
         println!("\talloc.add.default\tCreates default account");
-        println!("\taddcode <address> <bytecode>\tAssigns <code> to <account>");
+        println!("\talloc.add.random\tCreates a funded account with a new random secret key");
+        println!("\tcode <address> [fork]\tDisassemble the code of <address> with the opcodes of [fork] (default the configured one)");
+        println!("\tcfg <address> <file> [fork]\tWrites the control flow graph of the code of <address> to <file> in Graphviz DOT format, highlighting the code executed by the last run");
+        println!("\taddcode <address> <bytecode>\tAssigns <code> to <account>, either hex bytecode or assembly (PUSH1 0x01 PUSH @label label: JUMPDEST ...)");
+        println!("\taddeof <address> <sections>\tAssigns an EOF container to <account>, sections separated by `|`: [<inputs>:<outputs>] <code> | container <hex> | data <hex>");
         println!("\tenv\t\t\tShow current environment");
         println!("\tenv.set.difficulty <difficulty>\t\t\tSet current difficulty");
         println!("\tenv.set.currentrandom [currentRandom]\tSet currentRandom for post-Merge transactions");
+        println!("\tenv.set.excessblobgas <excess>\tSet currentExcessBlobGas");
+        println!("\tenv.set.parentblobgas <excess> <used>\tSet parent excess blob gas and blob gas used, currentExcessBlobGas is computed from them when not set");
+        println!("\tenv.set.beaconroot <root>\tSet parentBeaconBlockRoot");
+        println!("\tenv.set.parentgas <base fee> <gas used> <gas limit>\tSet parent gas values, currentBaseFee is computed from them");
+        println!("\tenv.set.parentdifficulty <difficulty> <timestamp> [uncle hash]\tSet parent difficulty values, currentDifficulty is computed from them");
+        println!("\tenv.next\t\t\tMove the environment to the block following the last run");
+        println!("\tenv.add.withdrawal <address> <amount> [validator index]\tAdd a withdrawal of <amount> Gwei to <address>");
+        println!("\tenv.remove.withdrawal <ix>\tRemove the withdrawal at position <ix>");
+        println!("\tenv.set.blockhash <number> <hash>\tSet the hash BLOCKHASH returns for block <number>");
+        println!("\tenv.add.ommer <address> [delta]\tAdd an ommer mined by <address>, <delta> blocks older than the current one (default 1)");
+        println!("\tenv.remove.ommer <ix>\t\tRemove the ommer at position <ix>");
         println!("\ttxs\t\t\tShow current transactions");
         println!("\ttx.new\t\t\tCreate (empty) transaction");
-        println!("\ttx.set.sender <ix> <address>\tSet <address>'s secret key in transaction with index <ix>");
+        println!("\ttx.set.sender <ix> <address|secret key>\tSet <address>'s secret key in transaction with index <ix>");
         println!("\ttx.set.receiver <ix>\tSet <address> as the receiver in transaction with index <ix>"); 
         println!("\ttx.set.input <ix> <input>\t Set transaction data (<input>) in transaction with index <ix>");
         println!("\ttx.set.value <ix> <value>\t Set transaction <value> in transaction with index <ix>");
+        println!("\ttx.set.type <ix> <type>\t Set transaction type: 0 (legacy), 1 (access list), 2 (dynamic fee), 3 (blob) or 4 (set code)");
+        println!("\ttx.set.gasprice <ix> <price>\t Set gasPrice of a legacy or type 1 transaction");
+        println!("\ttx.set.maxfee <ix> <fee>\t Set maxFeePerGas of a type 2 transaction");
+        println!("\ttx.set.priorityfee <ix> <fee>\t Set maxPriorityFeePerGas of a type 2 transaction");
+        println!("\ttx.add.accesslist <ix> <address> [storage keys]\t Add <address> and its storage keys to the access list");
+        println!("\ttx.set.blobfee <ix> <fee>\t Set maxFeePerBlobGas of a type 3 transaction");
+        println!("\ttx.add.blobhash <ix> <hash>\t Add a blob versioned hash to a type 3 transaction");
+        println!("\ttx.add.authorization <ix> <authority> <delegate> [nonce]\t Sign with <authority>'s key a delegation to <delegate> code and add it to a type 4 transaction");
+        println!("\ttx.sign <ix>\t\tSign transaction with index <ix> locally, replacing its secret key by v, r, s");
         println!("\trun\t\t\tExecute test case");
+        println!("\ttracediff <left> <right> [context]\tShow the first step where two EIP-3155 trace files differ, with [context] steps around it (default 3)");
+        println!("\tdiff-run <t8n> [evm]\tExecute test case with the configured t8n tool and with <t8n> and <evm>, and show the differences");
         println!("\tsave <filename>\t\tSaves current session to json file");
         println!("\tload <filename>\t\tReload previous session from json file");
         println!("\texit\t\t\tExit");
     }
 
     fn cmd_unknown(&self) {
-        panic!("unknown command!");
+        println!("Command not found!");
     }
 
     fn cmd_exit(&self) -> bool{
         println!("Bye!");
-        return true;
+        true
     }
 
     fn cmd_extract(&self, ctx: &mut Context){
-        if self.command_params.len() != 1 {
-            println!("Error: 1 parameter expected (state test json file)");
+        if self.command_params.is_empty() || self.command_params.len() > 3 {
+            println!("Error: 1 to 3 parameters expected (state test json file, [test name], [data,gas,value])");
             return;
         }
 
-        let c = Context::from_state_test(self.command_params[0].as_str());
+        let fname = self.command_params[0].as_str();
+        let mut test_name: Option<&str> = None;
+        let mut indexes: Option<Indexes> = None;
+        for param in self.command_params.iter().skip(1) {
+            match Indexes::parse(param) {
+                Some(ix) => indexes = Some(ix),
+                None => test_name = Some(param.as_str())
+            }
+        }
 
-        if c.is_ok() {
-            let new_context = c.unwrap();
-            ctx.alloc = new_context.alloc;
-            ctx.env = new_context.env;
-            ctx.txs = new_context.txs;
-            println!("Context information extracted correctly!");
-        } else {
-            println!("Error, cannot open file `{}`", self.command_params[0]);
+        if test_name.is_none() {
+            match Context::list_state_tests(fname) {
+                Ok(names) if names.len() > 1 => {
+                    println!("File `{}` contains {} tests, select one with `extract {} <test>`:", fname, names.len(), fname);
+                    for name in names {
+                        println!("\t{}", name);
+                    }
+                    return;
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    println!("Error extracting `{}`: {}", fname, e);
+                    return;
+                }
+            }
+        }
+
+        let indexes = match indexes {
+            Some(ix) => ix,
+            None => match Context::state_test_indexes(fname, test_name, ctx.config.hard_fork) {
+                Ok(cases) if cases.len() == 1 => cases[0].clone(),
+                Ok(cases) if cases.is_empty() => {
+                    println!("Test has no post expectations for `{}`, using indexes 0,0,0", ctx.config.hard_fork);
+                    Indexes { data: 0, gas: 0, value: 0 }
+                },
+                Ok(cases) => {
+                    println!("Test has {} cases for `{}`, select one with `extract {} [test] <data,gas,value>`:", cases.len(), ctx.config.hard_fork, fname);
+                    for ix in cases {
+                        println!("\t{},{},{}", ix.data, ix.gas, ix.value);
+                    }
+                    return;
+                },
+                Err(e) => {
+                    println!("Error extracting `{}`: {}", fname, e);
+                    return;
+                }
+            }
+        };
+
+        match Context::from_state_test(fname, test_name, &indexes) {
+            Ok(new_context) => {
+                ctx.alloc = new_context.alloc;
+                ctx.env = new_context.env;
+                ctx.txs = new_context.txs;
+                ctx.expect = new_context.expect;
+                println!("Context information extracted correctly!");
+            },
+            Err(e) => println!("Error extracting `{}`: {}", fname, e)
         }
     }
 
@@ -112,19 +243,90 @@ impl ReplCommand {
         }
     }
 
+    fn save_config(&self, ctx: &Context) {
+        if let Err(e) = ctx.config.save() {
+            println!("Error saving configuration: {}", e);
+        }
+    }
+
     fn cmd_set_hard_fork(&self, ctx: &mut Context) {
         if self.command_params.len() != 1 {
             println!("Error: 1 parameter expected (HardFork name)");
             return;
         }
 
-        ctx.config.hard_fork = self.command_params[0].clone();
-        ctx.config.save();
-        println!("HardFork `{}` configured!", ctx.config.hard_fork);
+        match self.command_params[0].parse() {
+            Ok(hard_fork) => {
+                ctx.config.hard_fork = hard_fork;
+                ctx.env.apply_fork_defaults(hard_fork);
+                self.save_config(ctx);
+                println!("HardFork `{}` configured!", ctx.config.hard_fork);
+            },
+            Err(e) => println!("Error: {}", e)
+        }
     }
 
     fn cmd_run(&self, ctx: &mut Context) {
-        ctx.run();
+        match ctx.run() {
+            Ok(output) => {
+                for trace in &output.traces {
+                    trace.print();
+                }
+                output.result.print();
+                output.print_delegations();
+                if ctx.expect.is_some() {
+                    match ctx.verify(&output.result) {
+                        Ok(verification) => verification.print(),
+                        Err(e) => println!("Cannot verify post state: {}", e)
+                    }
+                }
+                ctx.last_result = Some(output.result);
+                ctx.last_traces = output.traces;
+            },
+            Err(e) => println!("Error: {}", e)
+        }
+    }
+
+    fn cmd_diff_run(&self, ctx: &mut Context) {
+        if self.command_params.is_empty() || self.command_params.len() > 2 {
+            println!("Error: expected 1 or 2 parameters (t8n path, evm)");
+            return;
+        }
+        let mut other = ctx.config.clone();
+        other.t8n = self.command_params[0].clone();
+        other.evm = self.command_params.get(1).cloned().unwrap_or_default();
+        match ctx.diff_run(&other) {
+            Ok((left, _, report)) => {
+                left.result.print();
+                report.print();
+                ctx.last_result = Some(left.result);
+                ctx.last_traces = left.traces;
+            },
+            Err(e) => println!("Error: {}", e)
+        }
+    }
+
+    fn cmd_trace_diff(&self) {
+        if self.command_params.len() < 2 || self.command_params.len() > 3 {
+            println!("Error: expected 2 or 3 parameters (left trace, right trace, context)");
+            return;
+        }
+        let context = match self.command_params.get(2).map(|c| c.parse::<usize>()) {
+            Some(Ok(context)) => context,
+            Some(Err(_)) => {
+                println!("Context {} is not valid!", self.command_params[2]);
+                return;
+            },
+            None => 3
+        };
+        let load = |param: &String| Trace::from_jsonl(Path::new(param), 0, String::new());
+        match (load(&self.command_params[0]), load(&self.command_params[1])) {
+            (Ok(left), Ok(right)) => match trace_diff::first_divergence(&left, &right, context) {
+                Some(divergence) => divergence.print(),
+                None => println!("Traces are identical ({} steps)", left.steps.len())
+            },
+            (Err(e), _) | (_, Err(e)) => println!("Error: {}", e)
+        }
     }
 
     fn cmd_set_t8n(&self, ctx: &mut Context) {
@@ -133,7 +335,7 @@ impl ReplCommand {
         return;
       }
       ctx.config.t8n = self.command_params[0].clone();
-      ctx.config.save();
+      self.save_config(ctx);
       println!("Configured t8n tool {}", ctx.config.t8n);
     }
 
@@ -148,7 +350,7 @@ impl ReplCommand {
       } else {
         ctx.config.evm = self.command_params[0].clone();
       }
-      ctx.config.save();
+      self.save_config(ctx);
       println!("Configured evm {}", ctx.config.evm);
     }
 
@@ -158,7 +360,9 @@ impl ReplCommand {
             return;
         }
 
-        ctx.env.set_current_difficulty(self.command_params[0].clone());
+        if let Some(difficulty) = self.typed_param(0) {
+            ctx.env.set_current_difficulty(difficulty);
+        }
     }
 
     fn cmd_env_set_current_random(&self, ctx: &mut Context) {
@@ -167,58 +371,373 @@ impl ReplCommand {
             return;
         }
 
-        let mut current_random = "0x0000000000000000000000000000000000000000000000000000000000020000".to_string();
+        let current_random = match self.command_params.len() {
+            1 => match self.typed_param(0) {
+                Some(current_random) => current_random,
+                None => return
+            },
+            _ => B256::from(U256::from(0x020000))
+        };
+        ctx.env.set_current_random(current_random);
+    }
 
-        if self.command_params.len() == 1 {
-            current_random = self.command_params[0].clone();
+    fn cmd_env_set_excess_blob_gas(&self, ctx: &mut Context) {
+        if self.check_params(1, "excess blob gas").is_err() {
+            return;
+        }
+        if let Some(excess_blob_gas) = self.typed_param(0) {
+            ctx.env.set_current_excess_blob_gas(excess_blob_gas);
+        }
+    }
+
+    fn cmd_env_set_parent_blob_gas(&self, ctx: &mut Context) {
+        if self.check_params(2, "parent excess blob gas, parent blob gas used").is_err() {
+            return;
+        }
+        if let (Some(excess), Some(used)) = (self.typed_param(0), self.typed_param(1)) {
+            ctx.env.set_parent_blob_gas(excess, used);
+            match ctx.env.excess_blob_gas(ctx.config.hard_fork) {
+                Ok(Some(excess_blob_gas)) => println!("currentExcessBlobGas: {}", excess_blob_gas),
+                Ok(None) => {},
+                Err(e) => println!("Error: {}", e)
+            }
+        }
+    }
+
+    fn cmd_env_set_parent_gas(&self, ctx: &mut Context) {
+        if self.check_params(3, "parent base fee, parent gas used, parent gas limit").is_err() {
+            return;
+        }
+        if let (Some(base_fee), Some(gas_used), Some(gas_limit)) = (self.typed_param(0), self.typed_param(1), self.typed_param(2)) {
+            ctx.env.set_parent_gas(base_fee, gas_used, gas_limit);
+            match ctx.env.base_fee(ctx.config.hard_fork) {
+                Ok(Some(base_fee)) => println!("currentBaseFee: {}", base_fee),
+                Ok(None) => {},
+                Err(e) => println!("Error: {}", e)
+            }
+        }
+    }
+
+    fn cmd_env_set_parent_difficulty(&self, ctx: &mut Context) {
+        if self.command_params.len() < 2 || self.command_params.len() > 3 {
+            println!("Error: expected 2 or 3 parameters (parent difficulty, parent timestamp, [parent uncle hash])");
+            return;
+        }
+        let uncle_hash = match self.command_params.len() {
+            3 => match self.typed_param(2) {
+                Some(uncle_hash) => Some(uncle_hash),
+                None => return
+            },
+            _ => None
+        };
+        if let (Some(difficulty), Some(timestamp)) = (self.typed_param(0), self.typed_param(1)) {
+            ctx.env.set_parent_difficulty(difficulty, timestamp, uncle_hash);
+            match ctx.env.difficulty(ctx.config.hard_fork) {
+                Ok(Some(difficulty)) => println!("currentDifficulty: {}", difficulty),
+                Ok(None) => {},
+                Err(e) => println!("Error: {}", e)
+            }
+        }
+    }
+
+    fn cmd_env_next(&self, ctx: &mut Context) {
+        let result = match &ctx.last_result {
+            Some(result) => result,
+            None => {
+                println!("Error: no block executed yet, use run first");
+                return;
+            }
+        };
+        let fork = ctx.config.hard_fork;
+        ctx.env = match ctx.env.next_block(result, fork) {
+            Ok(env) => env,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        };
+        ctx.last_result = None;
+        println!("currentNumber: {} currentTimestamp: {}", ctx.env.current_number, ctx.env.current_timestamp);
+        println!("previousHash: unknown, set the hash of block {} with env.set.blockhash if BLOCKHASH needs it", ctx.env.current_number - U256::from(1));
+        match ctx.env.base_fee(fork) {
+            Ok(Some(base_fee)) => println!("currentBaseFee: {}", base_fee),
+            Ok(None) => {},
+            Err(e) => println!("Error: {}", e)
+        }
+        match ctx.env.difficulty(fork) {
+            Ok(Some(difficulty)) => println!("currentDifficulty: {}", difficulty),
+            Ok(None) => {},
+            Err(e) => println!("Error: {}", e)
+        }
+    }
+
+    fn cmd_env_set_beacon_root(&self, ctx: &mut Context) {
+        if self.check_params(1, "parent beacon block root").is_err() {
+            return;
+        }
+        if let Some(root) = self.typed_param(0) {
+            ctx.env.set_parent_beacon_block_root(root);
+        }
+    }
+
+    fn cmd_env_add_withdrawal(&self, ctx: &mut Context) {
+        if self.command_params.len() < 2 || self.command_params.len() > 3 {
+            println!("Error: Expected 2 or 3 parameters (address, amount, validator index)");
+            return;
+        }
+        let (address, amount) = match (self.typed_param(0), self.typed_param(1)) {
+            (Some(address), Some(amount)) => (address, amount),
+            _ => return
+        };
+        let validator_index = match self.command_params.len() {
+            3 => match self.typed_param(2) {
+                Some(validator_index) => validator_index,
+                None => return
+            },
+            _ => U256::ZERO
+        };
+        let withdrawal = ctx.env.add_withdrawal(validator_index, address, amount);
+        println!("Withdrawal {} added", withdrawal.index);
+    }
+
+    fn cmd_env_remove_withdrawal(&self, ctx: &mut Context) {
+        if self.check_params(1, "position").is_err() {
+            return;
+        }
+        let ix = match self.command_params[0].parse::<usize>() {
+            Ok(ix) => ix,
+            Err(_) => {
+                println!("Index {} is not valid!", self.command_params[0]);
+                return;
+            }
+        };
+        match ctx.env.remove_withdrawal(ix) {
+            Ok(withdrawal) => println!("Withdrawal {} removed", withdrawal.index),
+            Err(e) => println!("Error: {}", e)
+        }
+    }
+
+    fn cmd_env_set_block_hash(&self, ctx: &mut Context) {
+        if self.check_params(2, "block number, block hash").is_err() {
+            return;
+        }
+        if let (Some(number), Some(hash)) = (self.typed_param(0), self.typed_param(1)) {
+            if let Err(e) = ctx.env.set_block_hash(number, hash) {
+                println!("Error: {}", e);
+            }
+        }
+    }
+
+    fn cmd_env_add_ommer(&self, ctx: &mut Context) {
+        if self.command_params.is_empty() || self.command_params.len() > 2 {
+            println!("Error: Expected 1 or 2 parameters (address, delta)");
+            return;
+        }
+        let address = match self.typed_param(0) {
+            Some(address) => address,
+            None => return
+        };
+        let delta = match self.command_params.len() {
+            2 => match self.typed_param::<U256>(1).map(|d| d.to_u64()) {
+                Some(Some(delta)) => delta,
+                Some(None) => {
+                    println!("Error: delta {} too big", self.command_params[1]);
+                    return;
+                },
+                None => return
+            },
+            _ => 1
+        };
+        match ctx.env.add_ommer(delta, address) {
+            Ok(ommer) => println!("Ommer {} added with delta {}", ommer.address, ommer.delta),
+            Err(e) => println!("Error: {}", e)
+        }
+    }
+
+    fn cmd_env_remove_ommer(&self, ctx: &mut Context) {
+        if self.check_params(1, "position").is_err() {
+            return;
+        }
+        let ix = match self.command_params[0].parse::<usize>() {
+            Ok(ix) => ix,
+            Err(_) => {
+                println!("Index {} is not valid!", self.command_params[0]);
+                return;
+            }
+        };
+        match ctx.env.remove_ommer(ix) {
+            Ok(ommer) => println!("Ommer {} removed", ommer.address),
+            Err(e) => println!("Error: {}", e)
         }
-        ctx.env.set_current_random(current_random);
     }
 
     fn cmd_add_account(&self, ctx: &mut Context) {
-        let mut address = "0x0000000000000000000000000000000000000100";
-        if self.command_params.len() == 1 {
-            address = self.command_params[0].as_str();
-        } 
         if self.command_params.len() > 1 {
-            println!("Error: Expected a maximum of 1 parameter (address)");
+            println!("Error: Expected a maximum of 1 parameter (address or secret key)");
+            return;
+        }
+        // A 32 byte parameter is a secret key, the address is derived from it
+        if let Some(secret_key) = self.command_params.first().and_then(|p| p.parse::<B256>().ok()) {
+            self.add_secret_key(ctx, secret_key);
             return;
         }
+        let address = match self.command_params.len() {
+            1 => match self.typed_param(0) {
+                Some(address) => address,
+                None => return
+            },
+            _ => Address::from_low_u64(0x100)
+        };
 
-        if ctx.add_address(address).is_ok() {
-            println!("New address added with default fields");
-        } else {
-            println!("Error creating new address");
+        match ctx.add_address(address) {
+            Ok(()) => println!("New address added with default fields"),
+            Err(e) => println!("Error creating new address: {}", e)
+        }
+    }
+
+    fn add_secret_key(&self, ctx: &mut Context, secret_key: B256) {
+        match ctx.add_secret_key(secret_key) {
+            Ok(address) => println!("Added address {}", address),
+            Err(e) => println!("Error creating new address: {}", e)
         }
     }
 
     fn cmd_add_default_account(&self, ctx: &mut Context) {
-        if ctx.add_default_address().is_ok() {
-            println!("Added default address");
-        } else {
-            println!("Error creating default address");
+        match ctx.add_default_address() {
+            Ok(address) => println!("Added default address {}", address),
+            Err(e) => println!("Error creating default address: {}", e)
         }
     }
 
+    fn cmd_add_random_account(&self, ctx: &mut Context) {
+        let secret_key = crypto::random_secret_key();
+        println!("Secret key {}", secret_key);
+        self.add_secret_key(ctx, secret_key);
+    }
+
     fn cmd_add_code(&self, ctx: &mut Context) {
-        if self.command_params.len() != 2 {
+        if self.command_params.len() < 2 {
             println!("Error: Expected 2 parameters (address, code)");
             return;
         }
-        let address = self.command_params[0].clone();
-        let code = self.command_params[1].clone();
+        let address = match self.typed_param::<Address>(0) {
+            Some(address) => address,
+            None => return
+        };
+        let code = match asm::parse_code(&self.command_params[1..].join(" ")) {
+            Ok(code) => code,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        };
 
-        if ctx.alloc.contains_key(&address) {
-            let mut account: Alloc = ctx.alloc.get(&address).unwrap().clone();
-            if account.set_code(code).is_ok() {
-                if ctx.alloc.remove(&address).is_some() {
-                    ctx.alloc.insert(address, account.to_owned());
+        match ctx.alloc.get_mut(&address) {
+            Some(account) => {
+                if let Err(e) = account.set_code(code) {
+                    println!("Error setting account's code: {}", e);
                 }
-            } else {
-                println!("Error setting account's code");
+                print_eof_status(account);
+            },
+            None => println!("Address {} not found!", address)
+        }
+    }
+
+    // Sections are separated by `|`: `data <hex>`, `container <hex>` or a code section
+    // `[<inputs>:<outputs>] <code>`, code sections default to 0 inputs and non-returning
+    fn cmd_add_eof(&self, ctx: &mut Context) {
+        if self.command_params.len() < 2 {
+            println!("Error: Expected 2 parameters (address, sections)");
+            return;
+        }
+        let address = match self.typed_param::<Address>(0) {
+            Some(address) => address,
+            None => return
+        };
+        let mut builder = ContainerBuilder::new();
+        for section in self.command_params[1..].join(" ").split('|') {
+            let section = section.trim();
+            let (kind, rest) = section.split_once(char::is_whitespace).unwrap_or((section, ""));
+            let added = match kind {
+                "data" => rest.parse::<Bytes>().map(|data| { builder.set_data(data.0); }),
+                "container" => rest.parse::<Bytes>().map(|container| { builder.add_container(container.0); }),
+                _ => parse_code_section(section).map(|(inputs, outputs, code)| { builder.add_code_section(inputs, outputs, code.0); })
+            };
+            if let Err(e) = added {
+                println!("Error: section `{}`: {}", section, e);
+                return;
             }
-        } else {
-            println!("Address {} not found!", address);
+        }
+        let code = match builder.build() {
+            Ok(code) => code,
+            Err(e) => {
+                println!("Error: invalid EOF container, {}", e);
+                return;
+            }
+        };
+        match ctx.alloc.get_mut(&address) {
+            Some(account) => {
+                if let Err(e) = account.set_code(code) {
+                    println!("Error setting account's code: {}", e);
+                }
+                print_eof_status(account);
+            },
+            None => println!("Address {} not found!", address)
+        }
+    }
+
+    fn cmd_code(&self, ctx: &mut Context) {
+        if self.command_params.is_empty() || self.command_params.len() > 2 {
+            println!("Error: Expected 1 or 2 parameters (address, fork)");
+            return;
+        }
+        let address = match self.typed_param::<Address>(0) {
+            Some(address) => address,
+            None => return
+        };
+        let fork = match self.command_params.len() {
+            2 => match self.typed_param::<HardFork>(1) {
+                Some(fork) => fork,
+                None => return
+            },
+            _ => ctx.config.hard_fork
+        };
+        match ctx.alloc.get(&address) {
+            Some(account) => match (account.delegation(), account.eof()) {
+                (Some(target), _) => println!("Delegated to {}", target),
+                (None, Some(Ok(container))) => println!("{}", container.listing()),
+                (None, Some(Err(e))) => println!("Invalid EOF container, {}", e),
+                (None, None) if account.code.is_empty() => println!("Address {} has no code", address),
+                (None, None) => println!("{}", account.disassemble(fork))
+            },
+            None => println!("Address {} not found!", address)
+        }
+    }
+
+    fn cmd_cfg(&self, ctx: &Context) {
+        if self.command_params.len() < 2 || self.command_params.len() > 3 {
+            println!("Error: Expected 2 or 3 parameters (address, file, fork)");
+            return;
+        }
+        let address = match self.typed_param::<Address>(0) {
+            Some(address) => address,
+            None => return
+        };
+        let fork = match self.command_params.len() {
+            3 => match self.typed_param::<HardFork>(2) {
+                Some(fork) => fork,
+                None => return
+            },
+            _ => ctx.config.hard_fork
+        };
+        let traces = if ctx.last_traces.is_empty() { None } else { Some(ctx.last_traces.as_slice()) };
+        let file = &self.command_params[1];
+        match ctx.cfg_dot(address, fork, traces) {
+            Ok(dot) => match fs::write(file, dot) {
+                Ok(_) => println!("Control flow graph of {} written to {}", address, file),
+                Err(e) => println!("Error: {}", Error::io(file, e))
+            },
+            Err(e) => println!("Error: {}", e)
         }
     }
 
@@ -230,25 +749,20 @@ impl ReplCommand {
         if self.check_params(2, "index, address").is_err() {
             return;
         }
-        let index = self.command_params[0].clone();
-        let index = index.parse::<i32>();
-
-        if index.is_ok() {
-            let index = index.unwrap();
-            let address = self.command_params[1].as_str();
-            if ctx.txs.len() > (index as usize) && index >= 0 {
-                let pk = ctx.get_secret_key(address.to_string());
-
-                if pk.is_some() {
-                    ctx.txs[index as usize].set_private_key(pk.unwrap().as_str());
-                } else {
-                    println!("Account not found or does not contain private key");
-                }
-            } else {
-                println!("Transaction not found!");
+        if let Some(index) = self.tx_index(0, ctx) {
+            // Either a secret key or the address of an account holding one
+            if let Ok(secret_key) = self.command_params[1].parse::<B256>() {
+                ctx.txs[index].set_private_key(secret_key);
+                return;
+            }
+            let address = match self.typed_param(1) {
+                Some(address) => address,
+                None => return
+            };
+            match ctx.get_secret_key(&address) {
+                Some(pk) => ctx.txs[index].set_private_key(pk),
+                None => println!("Account not found or does not contain private key")
             }
-        } else {
-            println!("Index {} is not valid!", self.command_params[0]);
         }
     }
 
@@ -256,20 +770,11 @@ impl ReplCommand {
         if self.check_params(2, "index, address").is_err() {
             return;
         }
-        let index = self.command_params[0].clone();
-        let index = index.parse::<i32>();
-
-        if index.is_ok() {
-            let index = index.unwrap();
-            let address = self.command_params[1].as_str();
-            if ctx.txs.len() > (index as usize) && index >= 0 {
-                ctx.txs[index as usize].set_receiver(address);
+        if let Some(index) = self.tx_index(0, ctx) {
+            if let Some(value) = self.typed_param(1) {
+                ctx.txs[index].set_receiver(value);
                 println!("Receiver configured!");
-            } else {
-                println!("Transaction not found!");
             }
-        } else {
-            println!("Index {} is not valid!", self.command_params[0]);
         }
     }
 
@@ -277,21 +782,11 @@ impl ReplCommand {
         if self.check_params(2, "index, input data").is_err() {
             return;
         }
-
-        let index = self.command_params[0].clone();
-        let index = index.parse::<i32>();
-
-        if index.is_ok() {
-            let index = index.unwrap();
-            let input_data = self.command_params[1].as_str();
-            if ctx.txs.len() > (index as usize) && index >= 0 {
-                ctx.txs[index as usize].set_input(input_data);
+        if let Some(index) = self.tx_index(0, ctx) {
+            if let Some(value) = self.typed_param(1) {
+                ctx.txs[index].set_input(value);
                 println!("Transaction input configured!");
-            } else {
-                println!("Transaction not found!");
             }
-        } else {
-            println!("Index {} is not valid!", self.command_params[0]);
         }
     }
 
@@ -299,24 +794,140 @@ impl ReplCommand {
         if self.check_params(2, "index, value").is_err() {
             return;
         }
+        if let Some(index) = self.tx_index(0, ctx) {
+            if let Some(value) = self.typed_param(1) {
+                ctx.txs[index].set_value(value);
+                println!("Transaction value configured!");
+            }
+        }
+    }
+
+    fn cmd_tx_set_type(&self, ctx: &mut Context) {
+        if self.check_params(2, "index, type").is_err() {
+            return;
+        }
+        if let (Some(index), Some(tx_type)) = (self.tx_index(0, ctx), self.typed_param::<U256>(1)) {
+            match ctx.txs[index].set_tx_type(tx_type.to_u64().unwrap_or(u64::MAX)) {
+                Ok(()) => println!("Transaction type configured!"),
+                Err(e) => println!("Error: {}", e)
+            }
+        }
+    }
+
+    fn cmd_tx_set_gas_price(&self, ctx: &mut Context) {
+        if self.check_params(2, "index, gas price").is_err() {
+            return;
+        }
+        if let (Some(index), Some(gas_price)) = (self.tx_index(0, ctx), self.typed_param(1)) {
+            ctx.txs[index].set_gas_price(gas_price);
+            println!("Transaction gas price configured!");
+        }
+    }
 
-        let index = self.command_params[0].clone();
-        let index = index.parse::<i32>();
+    fn cmd_tx_set_max_fee(&self, ctx: &mut Context) {
+        if self.check_params(2, "index, max fee per gas").is_err() {
+            return;
+        }
+        if let (Some(index), Some(max_fee)) = (self.tx_index(0, ctx), self.typed_param(1)) {
+            ctx.txs[index].set_max_fee_per_gas(max_fee);
+            println!("Transaction max fee per gas configured!");
+        }
+    }
 
-        if index.is_ok() {
-            let index = index.unwrap();
-            let value = self.command_params[1].as_str();
-            if ctx.txs.len() > (index as usize) && index >= 0 {
-                ctx.txs[index as usize].set_value(value);
-                println!("Transaction value configured!");
-            } else {
-                println!("Transaction not found!");
+    fn cmd_tx_set_priority_fee(&self, ctx: &mut Context) {
+        if self.check_params(2, "index, max priority fee per gas").is_err() {
+            return;
+        }
+        if let (Some(index), Some(priority_fee)) = (self.tx_index(0, ctx), self.typed_param(1)) {
+            ctx.txs[index].set_max_priority_fee_per_gas(priority_fee);
+            println!("Transaction max priority fee per gas configured!");
+        }
+    }
+
+    fn cmd_tx_add_access_list(&self, ctx: &mut Context) {
+        if self.command_params.len() < 2 {
+            println!("Error: Expected at least 2 parameters (index, address, storage keys)");
+            return;
+        }
+        let index = match self.tx_index(0, ctx) {
+            Some(index) => index,
+            None => return
+        };
+        let address = match self.typed_param(1) {
+            Some(address) => address,
+            None => return
+        };
+        let mut storage_keys: Vec<B256> = Vec::new();
+        for param in 2..self.command_params.len() {
+            match self.typed_param(param) {
+                Some(key) => storage_keys.push(key),
+                None => return
             }
-        } else {
-            println!("Index {} is not valid!", self.command_params[0]);
         }
+        ctx.txs[index].add_access_list_item(address, storage_keys);
+        println!("Access list configured!");
+    }
+
+    fn cmd_tx_set_blob_fee(&self, ctx: &mut Context) {
+        if self.check_params(2, "index, max fee per blob gas").is_err() {
+            return;
+        }
+        if let (Some(index), Some(blob_fee)) = (self.tx_index(0, ctx), self.typed_param(1)) {
+            ctx.txs[index].set_max_fee_per_blob_gas(blob_fee);
+            println!("Transaction max fee per blob gas configured!");
+        }
+    }
+
+    fn cmd_tx_add_blob_hash(&self, ctx: &mut Context) {
+        if self.check_params(2, "index, blob versioned hash").is_err() {
+            return;
+        }
+        if let (Some(index), Some(hash)) = (self.tx_index(0, ctx), self.typed_param(1)) {
+            ctx.txs[index].add_blob_versioned_hash(hash);
+            println!("Blob versioned hash added!");
+        }
+    }
 
+    fn cmd_tx_add_authorization(&self, ctx: &mut Context) {
+        if self.command_params.len() < 3 || self.command_params.len() > 4 {
+            println!("Error: Expected 3 or 4 parameters (index, authority, delegate, nonce)");
+            return;
+        }
+        let index = match self.tx_index(0, ctx) {
+            Some(index) => index,
+            None => return
+        };
+        let (authority, delegate) = match (self.typed_param::<Address>(1), self.typed_param(2)) {
+            (Some(authority), Some(delegate)) => (authority, delegate),
+            _ => return
+        };
+        let nonce = match self.command_params.len() {
+            4 => match self.typed_param(3) {
+                Some(nonce) => Some(nonce),
+                None => return
+            },
+            _ => None
+        };
+        match ctx.authorize(index, &authority, delegate, nonce) {
+            Ok(authorization) => {
+                println!("Authorization of {} with nonce {} added", authority, authorization.nonce);
+                ctx.txs[index].add_authorization(authorization);
+            },
+            Err(e) => println!("Error: {}", e)
+        }
+    }
 
+    fn cmd_tx_sign(&self, ctx: &mut Context) {
+        if self.check_params(1, "index").is_err() {
+            return;
+        }
+        if let Some(index) = self.tx_index(0, ctx) {
+            let fork = ctx.env.active_fork(ctx.config.hard_fork);
+            match ctx.txs[index].sign(fork) {
+                Ok(sender) => println!("Transaction signed by {}", sender),
+                Err(e) => println!("Error signing transaction: {}", e)
+            }
+        }
     }
 
     fn cmd_save(&self, ctx: &mut Context) {
@@ -324,10 +935,9 @@ impl ReplCommand {
            return
        }
        let fname = self.command_params[0].as_str();
-       if ctx.save(fname).is_ok() {
-           println!("Context saved {}", fname);
-       } else {
-           println!("Error saving context");
+       match ctx.save(fname) {
+           Ok(()) => println!("Context saved {}", fname),
+           Err(e) => println!("Error saving context: {}", e)
        }
     }
 
@@ -336,10 +946,9 @@ impl ReplCommand {
             return
         }
         let fname = self.command_params[0].as_str();
-        if ctx.load(fname).is_ok() {
-            println!("Context loaded correctly");
-        } else {
-            println!("Error loading context from file {}", fname);
+        match ctx.load(fname) {
+            Ok(()) => println!("Context loaded correctly"),
+            Err(e) => println!("Error loading context: {}", e)
         }
     }
 
@@ -351,26 +960,52 @@ impl ReplCommand {
             "alloc" => ctx.print_alloc(),
             "alloc.add" => self.cmd_add_account(ctx),
             "alloc.add.default" => self.cmd_add_default_account(ctx),
+            "alloc.add.random" => self.cmd_add_random_account(ctx),
             "addcode" => self.cmd_add_code(ctx),
+            "addeof" => self.cmd_add_eof(ctx),
+            "code" => self.cmd_code(ctx),
+            "cfg" => self.cmd_cfg(ctx),
             "env" => ctx.print_env(),
             "env.set.difficulty" => self.cmd_env_set_difficulty(ctx),
             "env.set.currentrandom" => self.cmd_env_set_current_random(ctx),
+            "env.set.excessblobgas" => self.cmd_env_set_excess_blob_gas(ctx),
+            "env.set.parentblobgas" => self.cmd_env_set_parent_blob_gas(ctx),
+            "env.set.beaconroot" => self.cmd_env_set_beacon_root(ctx),
+            "env.set.parentgas" => self.cmd_env_set_parent_gas(ctx),
+            "env.set.parentdifficulty" => self.cmd_env_set_parent_difficulty(ctx),
+            "env.next" => self.cmd_env_next(ctx),
+            "env.add.withdrawal" => self.cmd_env_add_withdrawal(ctx),
+            "env.set.blockhash" => self.cmd_env_set_block_hash(ctx),
+            "env.add.ommer" => self.cmd_env_add_ommer(ctx),
+            "env.remove.ommer" => self.cmd_env_remove_ommer(ctx),
+            "env.remove.withdrawal" => self.cmd_env_remove_withdrawal(ctx),
             "txs" => ctx.print_txs(),
             "tx.new" => self.cmd_new_tx(ctx),
             "tx.set.sender" => self.cmd_tx_set_sender(ctx),
             "tx.set.receiver" => self.cmd_tx_set_receiver(ctx),
             "tx.set.input" => self.cmd_tx_set_input(ctx),
             "tx.set.value" => self.cmd_tx_set_value(ctx),
+            "tx.set.type" => self.cmd_tx_set_type(ctx),
+            "tx.set.gasprice" => self.cmd_tx_set_gas_price(ctx),
+            "tx.set.maxfee" => self.cmd_tx_set_max_fee(ctx),
+            "tx.set.priorityfee" => self.cmd_tx_set_priority_fee(ctx),
+            "tx.add.accesslist" => self.cmd_tx_add_access_list(ctx),
+            "tx.set.blobfee" => self.cmd_tx_set_blob_fee(ctx),
+            "tx.add.blobhash" => self.cmd_tx_add_blob_hash(ctx),
+            "tx.add.authorization" => self.cmd_tx_add_authorization(ctx),
+            "tx.sign" => self.cmd_tx_sign(ctx),
             "hf" => self.cmd_set_hard_fork(ctx),
             "t8n" => self.cmd_set_t8n(ctx),
             "evm" => self.cmd_set_evm(ctx),
             "run" => self.cmd_run(ctx),
+            "diff-run" => self.cmd_diff_run(ctx),
+            "tracediff" => self.cmd_trace_diff(),
             "save" => self.cmd_save(ctx),
             "load" => self.cmd_load(ctx),
             "exit" => return self.cmd_exit(),
             _ => self.cmd_unknown()
         }
-        return false;
+        false
     }
 }
 
@@ -379,6 +1014,12 @@ pub struct Repl {
     context: Context
 }
 
+impl Default for Repl {
+    fn default() -> Self {
+        Repl::new()
+    }
+}
+
 impl Repl {
     pub fn new() -> Repl {
         let context = Context::default();
@@ -387,28 +1028,58 @@ impl Repl {
         }
     }
 
+    // Reads commands with line editing and tab completion, the history is kept in
+    // ~/.t8n-repl.history
     pub fn run(&mut self) -> io::Result<()> {
         self.welcome_message();
+        let mut editor: Editor<ReplHelper, FileHistory> = Editor::new().map_err(io::Error::other)?;
+        editor.set_helper(Some(ReplHelper::new()));
+        let history = match config::history_file_path() {
+            Ok(path) => Some(path),
+            Err(e) => {
+                println!("Warning: history will not be saved, {}", e);
+                None
+            }
+        };
+        if let Some(path) = history.as_ref().filter(|p| p.exists()) {
+            if let Err(e) = editor.load_history(path) {
+                println!("Warning: cannot read history {}: {}", path.display(), e);
+            }
+        }
+
+        let result = self.read_commands(&mut editor);
+        if let Some(path) = &history {
+            if let Err(e) = editor.save_history(path) {
+                println!("Warning: cannot write history {}: {}", path.display(), e);
+            }
+        }
+        result
+    }
+
+    fn read_commands(&mut self, editor: &mut Editor<ReplHelper, FileHistory>) -> io::Result<()> {
         loop {
-            let mut user_input = String::new();
-            let stdin = io::stdin();
-            let mut stdout = io::stdout();
-            let mut prompt = String::from(self.context.config.hard_fork.as_str());
-            prompt.push_str(" > ");
-            stdout.write(prompt.as_bytes());
-            stdout.flush();
-            stdin.read_line(&mut user_input)?;
-
-            let command = ReplCommand::from_string(user_input);
-
-            if command.is_some() {
-                let exit = command.unwrap().execute(&mut self.context);
-
-                if exit {
-                    return Ok(())
-                }
-            } else {
-                println!("Command not found!");
+            if let Some(helper) = editor.helper_mut() {
+                helper.update(&self.context);
+            }
+            let prompt = format!("{} > ", self.context.config.hard_fork);
+            let user_input = match editor.readline(&prompt) {
+                Ok(line) => line,
+                // Ctrl-C drops the current line
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(()),
+                Err(e) => return Err(io::Error::other(e))
+            };
+            if !user_input.trim().is_empty() {
+                editor.add_history_entry(user_input.as_str()).map_err(io::Error::other)?;
+            }
+
+            match ReplCommand::from_string(user_input) {
+                Some(command) => {
+                    if command.execute(&mut self.context) {
+                        return Ok(())
+                    }
+                },
+                None => println!("Command not found!")
             }
         }
     }
@@ -0,0 +1,74 @@
+// Minimal RLP encoder, enough to build the transaction signing payloads
+
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes: Vec<u8> = len.to_be_bytes().iter().copied().skip_while(|b| *b == 0).collect();
+    let mut out = vec![offset + 55 + len_bytes.len() as u8];
+    out.extend(len_bytes);
+    out
+}
+
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = encode_length(bytes.len(), 0x80);
+    out.extend_from_slice(bytes);
+    out
+}
+
+// Integers are encoded big endian without leading zeros, zero is the empty string
+pub fn encode_uint(be_bytes: &[u8]) -> Vec<u8> {
+    let start = be_bytes.iter().position(|b| *b != 0).unwrap_or(be_bytes.len());
+    encode_bytes(&be_bytes[start..])
+}
+
+// `items` are already encoded
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut out = encode_length(payload.len(), 0xc0);
+    out.extend(payload);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_strings() {
+        assert_eq!(encode_bytes(&[]), vec![0x80]);
+        assert_eq!(encode_bytes(&[0x7f]), vec![0x7f]);
+        assert_eq!(encode_bytes(&[0x80]), vec![0x81, 0x80]);
+        assert_eq!(encode_bytes(b"dog"), vec![0x83, b'd', b'o', b'g']);
+
+        let long = [0xaa; 56];
+        let encoded = encode_bytes(&long);
+        assert_eq!(encoded[..2], [0xb8, 56]);
+        assert_eq!(encoded[2..], long);
+    }
+
+    #[test]
+    fn encodes_integers_without_leading_zeros() {
+        assert_eq!(encode_uint(&[0, 0]), vec![0x80]);
+        assert_eq!(encode_uint(&[0, 0x0f]), vec![0x0f]);
+        assert_eq!(encode_uint(&1024u64.to_be_bytes()), vec![0x82, 0x04, 0x00]);
+    }
+
+    #[test]
+    fn encodes_lists() {
+        assert_eq!(encode_list(&[]), vec![0xc0]);
+        assert_eq!(encode_list(&[encode_bytes(b"cat"), encode_bytes(b"dog")]),
+                   vec![0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']);
+        // [ [], [[]], [ [], [[]] ] ]
+        let empty = encode_list(&[]);
+        let nested = encode_list(std::slice::from_ref(&empty));
+        assert_eq!(encode_list(&[empty.clone(), nested.clone(), encode_list(&[empty, nested])]),
+                   vec![0xc7, 0xc0, 0xc1, 0xc0, 0xc3, 0xc0, 0xc1, 0xc0]);
+
+        let long = encode_list(&vec![encode_bytes(&[0x01]); 60]);
+        assert_eq!(long[..2], [0xf8, 60]);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::context::{Trace, TraceStep};
+use crate::types::U256;
+
+// Fields of an EIP-3155 step taken into account when comparing traces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepField {
+    Pc,
+    Op,
+    Gas,
+    Stack,
+    MemSize,
+    Depth,
+    Refund
+}
+
+impl StepField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepField::Pc => "pc",
+            StepField::Op => "op",
+            StepField::Gas => "gas",
+            StepField::Stack => "stack",
+            StepField::MemSize => "memSize",
+            StepField::Depth => "depth",
+            StepField::Refund => "refund"
+        }
+    }
+}
+
+// Fields where two steps disagree
+pub fn step_differences(left: &TraceStep, right: &TraceStep) -> Vec<StepField> {
+    let checks = [
+        (StepField::Pc, left.pc == right.pc),
+        (StepField::Op, left.op == right.op),
+        (StepField::Gas, left.gas == right.gas),
+        (StepField::Stack, left.stack == right.stack),
+        (StepField::MemSize, left.mem_size == right.mem_size),
+        (StepField::Depth, left.depth == right.depth),
+        (StepField::Refund, left.refund == right.refund)
+    ];
+    checks.iter().filter(|(_, same)| !same).map(|(field, _)| *field).collect()
+}
+
+// Steps found at the same position of both traces, None once a trace has ended
+#[derive(Debug, Clone)]
+pub struct StepPair {
+    pub index: usize,
+    pub left: Option<TraceStep>,
+    pub right: Option<TraceStep>
+}
+
+// First step where two traces of the same transaction disagree, with up to `context`
+// steps before and after it
+#[derive(Debug, Clone)]
+pub struct TraceDivergence {
+    pub tx_index: usize,
+    // Empty when one of the traces ended before the other
+    pub fields: Vec<StepField>,
+    pub before: Vec<StepPair>,
+    pub at: StepPair,
+    pub after: Vec<StepPair>
+}
+
+fn pair(left: &[TraceStep], right: &[TraceStep], index: usize) -> StepPair {
+    StepPair { index, left: left.get(index).cloned(), right: right.get(index).cloned() }
+}
+
+// Walks both traces in lockstep and stops at the first step that differs in pc, op, gas,
+// stack, memory size, depth or refund, or where one of them ends
+pub fn first_divergence(left: &Trace, right: &Trace, context: usize) -> Option<TraceDivergence> {
+    let (l, r) = (&left.steps, &right.steps);
+    let (index, fields) = (0..l.len().max(r.len())).find_map(|ix| match (l.get(ix), r.get(ix)) {
+        (Some(ls), Some(rs)) => {
+            let fields = step_differences(ls, rs);
+            if fields.is_empty() { None } else { Some((ix, fields)) }
+        },
+        _ => Some((ix, Vec::new()))
+    })?;
+    let end = (index + 1 + context).min(l.len().max(r.len()));
+    Some(TraceDivergence {
+        tx_index: left.tx_index,
+        fields,
+        before: (index.saturating_sub(context)..index).map(|ix| pair(l, r, ix)).collect(),
+        at: pair(l, r, index),
+        after: (index + 1..end).map(|ix| pair(l, r, ix)).collect()
+    })
+}
+
+// First divergence over all the transactions of a block, traces are matched by
+// transaction index and a missing trace counts as an empty one
+pub fn first_block_divergence(left: &[Trace], right: &[Trace], context: usize) -> Option<TraceDivergence> {
+    let mut tx_indexes: Vec<usize> = left.iter().chain(right.iter()).map(|t| t.tx_index).collect();
+    tx_indexes.sort();
+    tx_indexes.dedup();
+    tx_indexes.into_iter().find_map(|tx_index| {
+        let find = |traces: &[Trace]| traces.iter().find(|t| t.tx_index == tx_index).cloned()
+            .unwrap_or(Trace { tx_index, tx_hash: String::new(), steps: Vec::new(), summary: None });
+        first_divergence(&find(left), &find(right), context)
+    })
+}
+
+fn step_row(marker: &str, index: usize, step: &Option<TraceStep>) -> String {
+    match step {
+        Some(step) => {
+            let op_name = step.op_name.clone().unwrap_or(format!("0x{:02x}", step.op));
+            let stack: Vec<String> = step.stack.iter().map(|v| v.to_string()).collect();
+            format!("{:<3}{:>6} {:>6} {:<14} gas: {:<10} cost: {:<8} depth: {} refund: {} mem: {} stack: [{}]",
+                marker, index, step.pc, op_name, step.gas, step.gas_cost, step.depth, step.refund, step.mem_size, stack.join(", "))
+        },
+        None => format!("{:<3}{:>6} end of trace", marker, index)
+    }
+}
+
+impl TraceDivergence {
+    pub fn step(&self) -> usize {
+        self.at.index
+    }
+
+    pub fn print(&self) {
+        let fields: Vec<&str> = self.fields.iter().map(|f| f.name()).collect();
+        let reason = if fields.is_empty() { "trace length differs".to_string() } else { format!("{} differ", fields.join(", ")) };
+        println!("First trace divergence in tx {} at step {}: {}", self.tx_index, self.at.index, reason);
+        for p in &self.before {
+            println!("{}", step_row("", p.index, &p.left));
+        }
+        println!("{}", step_row("L>", self.at.index, &self.at.left));
+        println!("{}", step_row("R>", self.at.index, &self.at.right));
+        if let (Some(l), Some(r)) = (&self.at.left, &self.at.right) {
+            for i in 0..l.stack.len().max(r.stack.len()) {
+                let (lv, rv) = (l.stack.get(i), r.stack.get(i));
+                if lv != rv {
+                    let show = |v: Option<&U256>| v.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string());
+                    println!("   stack[{}]: {} != {}", i, show(lv), show(rv));
+                }
+            }
+        }
+        for p in &self.after {
+            println!("{}", step_row("L", p.index, &p.left));
+            println!("{}", step_row("R", p.index, &p.right));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(pc: u64, op: u8, gas: u64) -> TraceStep {
+        TraceStep { pc, op, gas, gas_cost: 3, mem_size: 0, stack: Vec::new(), return_data: None, depth: 1, refund: 0, op_name: None, error: None }
+    }
+
+    fn trace(steps: Vec<TraceStep>) -> Trace {
+        Trace { tx_index: 0, tx_hash: String::new(), steps, summary: None }
+    }
+
+    // PUSH1 PUSH1 ADD POP STOP
+    fn steps() -> Vec<TraceStep> {
+        vec![step(0, 0x60, 100), step(2, 0x60, 97), step(4, 0x01, 94), step(5, 0x50, 91), step(6, 0x00, 89)]
+    }
+
+    #[test]
+    fn identical_traces_do_not_diverge() {
+        assert!(first_divergence(&trace(steps()), &trace(steps()), 2).is_none());
+        assert!(first_divergence(&trace(Vec::new()), &trace(Vec::new()), 2).is_none());
+    }
+
+    #[test]
+    fn reports_the_first_differing_step_and_fields() {
+        let mut right = steps();
+        right[2].gas = 95;
+        right[2].stack = vec![U256::from(1)];
+        right[3].pc = 7;
+        let divergence = first_divergence(&trace(steps()), &trace(right), 1).unwrap();
+        assert_eq!(divergence.step(), 2);
+        assert_eq!(divergence.fields, vec![StepField::Gas, StepField::Stack]);
+        assert_eq!(divergence.before.iter().map(|p| p.index).collect::<Vec<usize>>(), vec![1]);
+        assert_eq!(divergence.after.iter().map(|p| p.index).collect::<Vec<usize>>(), vec![3]);
+    }
+
+    #[test]
+    fn context_is_clamped_to_the_traces() {
+        let mut right = steps();
+        right[0].op = 0x61;
+        let divergence = first_divergence(&trace(steps()), &trace(right), 10).unwrap();
+        assert_eq!(divergence.step(), 0);
+        assert_eq!(divergence.fields, vec![StepField::Op]);
+        assert!(divergence.before.is_empty());
+        assert_eq!(divergence.after.len(), 4);
+    }
+
+    #[test]
+    fn a_shorter_trace_diverges_where_it_ends() {
+        let mut shorter = steps();
+        shorter.truncate(3);
+        let divergence = first_divergence(&trace(steps()), &trace(shorter), 1).unwrap();
+        assert_eq!(divergence.step(), 3);
+        assert!(divergence.fields.is_empty());
+        assert!(divergence.at.left.is_some());
+        assert!(divergence.at.right.is_none());
+        assert!(divergence.after[0].right.is_none());
+    }
+
+    #[test]
+    fn block_divergence_matches_traces_by_transaction() {
+        let first = trace(steps());
+        let mut second = trace(steps());
+        second.tx_index = 1;
+        let mut changed = second.clone();
+        changed.steps[4].refund = 4800;
+
+        assert!(first_block_divergence(&[first.clone(), second.clone()], &[first.clone(), second.clone()], 0).is_none());
+        let divergence = first_block_divergence(&[first.clone(), second.clone()], &[first.clone(), changed], 0).unwrap();
+        assert_eq!((divergence.tx_index, divergence.step()), (1, 4));
+        assert_eq!(divergence.fields, vec![StepField::Refund]);
+
+        // A missing trace counts as an empty one
+        let divergence = first_block_divergence(&[first.clone(), second], &[first], 0).unwrap();
+        assert_eq!((divergence.tx_index, divergence.step()), (1, 0));
+        assert!(divergence.at.right.is_none());
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Error;
+
+// Hex input is accepted with or without the `0x` prefix
+fn decode_hex(input: &str) -> Result<Vec<u8>, Error> {
+    let digits = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input);
+    hex::decode(digits).map_err(|e| Error::invalid(format!("Invalid hex `{}`: {}", input, e)))
+}
+
+fn decode_fixed<const N: usize>(input: &str, name: &str) -> Result<[u8; N], Error> {
+    let bytes = decode_hex(input)?;
+    bytes.try_into().map_err(|b: Vec<u8>| Error::invalid(format!("Invalid {} `{}`: expected {} bytes, got {}", name, input, N, b.len())))
+}
+
+macro_rules! impl_serde_from_str {
+    ($name:ident) => {
+        // Debug output is used to print the context, keep it in hex
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.to_string().as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$name, D::Error> {
+                let value = serde_json::Value::deserialize(deserializer)?;
+                match value {
+                    serde_json::Value::String(s) => s.parse().map_err(serde::de::Error::custom),
+                    // Only integers, a float such as 1e18 would otherwise be read as hex digits
+                    serde_json::Value::Number(n) => match n.as_u64() {
+                        Some(n) => n.to_string().parse().map_err(serde::de::Error::custom),
+                        None => Err(serde::de::Error::custom(format!(concat!("invalid ", stringify!($name), " {}, expected an unsigned integer"), n)))
+                    },
+                    _ => Err(serde::de::Error::custom(concat!("invalid ", stringify!($name))))
+                }
+            }
+        }
+    };
+}
+
+// 20 byte account address
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Address(pub [u8; 20]);
+
+impl Address {
+    // Precompile style addresses such as 0x...0100
+    pub fn from_low_u64(value: u64) -> Address {
+        let mut bytes = [0u8; 20];
+        bytes[12..].copy_from_slice(&value.to_be_bytes());
+        Address(bytes)
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Address, Error> {
+        Ok(Address(decode_fixed(s.trim(), "address")?))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl_serde_from_str!(Address);
+
+// 32 byte hash (state roots, block hashes, secret keys)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct B256(pub [u8; 32]);
+
+impl FromStr for B256 {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<B256, Error> {
+        Ok(B256(decode_fixed(s.trim(), "32 byte hash")?))
+    }
+}
+
+impl fmt::Display for B256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl From<U256> for B256 {
+    fn from(value: U256) -> B256 {
+        B256(value.to_be_bytes())
+    }
+}
+
+impl_serde_from_str!(B256);
+
+// Arbitrary length byte string (code, input data)
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct Bytes(pub Vec<u8>);
+
+impl Bytes {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Bytes, Error> {
+        Ok(Bytes(decode_hex(s.trim())?))
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(&self.0))
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Bytes {
+        Bytes(bytes)
+    }
+}
+
+impl_serde_from_str!(Bytes);
+
+// 256 bit quantity. Serialized as minimal `0x` hex (`0x0` for zero); parsed from `0x` hex,
+// decimal, or hex without prefix when it contains hex letters
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct U256(primitive_types::U256);
+
+impl U256 {
+    pub const ZERO: U256 = U256(primitive_types::U256::zero());
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.0.bits() > 64 {
+            return None;
+        }
+        Some(self.0.low_u64())
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        self.0.to_big_endian(&mut bytes);
+        bytes
+    }
+
+    pub fn from_be_bytes(bytes: &[u8]) -> U256 {
+        U256(primitive_types::U256::from_big_endian(bytes))
+    }
+}
+
+macro_rules! impl_u256_op {
+    ($trait:ident, $method:ident) => {
+        impl std::ops::$trait for U256 {
+            type Output = U256;
+
+            fn $method(self, rhs: U256) -> U256 {
+                U256(self.0.$method(rhs.0))
+            }
+        }
+    };
+}
+
+// Arithmetic panics on overflow and division by zero, like the integer types
+impl_u256_op!(Add, add);
+impl_u256_op!(Sub, sub);
+impl_u256_op!(Mul, mul);
+impl_u256_op!(Div, div);
+
+impl U256 {
+    pub fn saturating_sub(self, rhs: U256) -> U256 {
+        U256(self.0.saturating_sub(rhs.0))
+    }
+
+    pub fn checked_add(self, rhs: U256) -> Option<U256> {
+        self.0.checked_add(rhs.0).map(U256)
+    }
+
+    pub fn checked_mul(self, rhs: U256) -> Option<U256> {
+        self.0.checked_mul(rhs.0).map(U256)
+    }
+
+    pub fn pow2(exp: usize) -> U256 {
+        U256(primitive_types::U256::one() << exp)
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> U256 {
+        U256(primitive_types::U256::from(value))
+    }
+}
+
+impl FromStr for U256 {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<U256, Error> {
+        let s = s.trim();
+        let invalid = || Error::invalid(format!("Invalid quantity `{}`", s));
+        let (digits, radix) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => (hex, 16),
+            None if s.chars().all(|c| c.is_ascii_digit()) => (s, 10),
+            None => (s, 16)
+        };
+        if digits.is_empty() {
+            // `0x` is how some fillers write zero
+            return if radix == 16 && !s.is_empty() { Ok(U256::ZERO) } else { Err(invalid()) };
+        }
+        primitive_types::U256::from_str_radix(digits, radix).map(U256).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:x}", self.0)
+    }
+}
+
+impl_serde_from_str!(U256);
+
+// Fixtures write contract creation as `"to": ""`
+pub fn empty_string_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where D: Deserializer<'de>, T: FromStr, T::Err: fmt::Display {
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    match value {
+        Some(s) if !s.is_empty() => s.parse().map(Some).map_err(serde::de::Error::custom),
+        _ => Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(s: &str) -> Result<U256, Error> {
+        s.parse()
+    }
+
+    #[test]
+    fn parses_quantities() {
+        assert_eq!(quantity("1000").unwrap(), U256::from(1000));
+        assert_eq!(quantity("0x3e8").unwrap(), U256::from(1000));
+        assert_eq!(quantity("0X3E8").unwrap(), U256::from(1000));
+        assert_eq!(quantity(" 0x3e8 ").unwrap(), U256::from(1000));
+        // Unprefixed input is hex only when it has hex letters
+        assert_eq!(quantity("3e8").unwrap(), U256::from(1000));
+        assert_eq!(quantity("10").unwrap(), U256::from(10));
+        assert_eq!(quantity("0x").unwrap(), U256::ZERO);
+        assert_eq!(quantity("0x0").unwrap(), U256::ZERO);
+        assert_eq!(quantity(&format!("0x{}", "f".repeat(64))).unwrap().to_be_bytes(), [0xff; 32]);
+
+        assert!(quantity("").is_err());
+        assert!(quantity("0xzz").is_err());
+        assert!(quantity("-1").is_err());
+        assert!(quantity(&format!("0x1{}", "0".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn deserializes_quantities() {
+        let parse = |json: &str| serde_json::from_str::<U256>(json);
+        assert_eq!(parse("1000").unwrap(), U256::from(1000));
+        assert_eq!(parse("\"1000\"").unwrap(), U256::from(1000));
+        assert_eq!(parse("\"0x3e8\"").unwrap(), U256::from(1000));
+        assert_eq!(parse("\"0x\"").unwrap(), U256::ZERO);
+        assert_eq!(parse(&u64::MAX.to_string()).unwrap(), U256::from(u64::MAX));
+
+        let float = parse("1e18").unwrap_err().to_string();
+        assert!(float.contains("expected an unsigned integer"), "{}", float);
+        assert!(parse("1.5").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("true").is_err());
+    }
+
+    #[test]
+    fn serializes_canonical_hex() {
+        let json = |value: &U256| serde_json::to_string(value).unwrap();
+        assert_eq!(json(&U256::ZERO), "\"0x0\"");
+        assert_eq!(json(&U256::from(1000)), "\"0x3e8\"");
+        assert_eq!(json(&quantity("0x000001").unwrap()), "\"0x1\"");
+
+        let address: Address = "0X000000000000000000000000000000000000ABCD".parse().unwrap();
+        assert_eq!(address, Address::from_low_u64(0xabcd));
+        assert_eq!(serde_json::to_string(&address).unwrap(), "\"0x000000000000000000000000000000000000abcd\"");
+        assert_eq!(serde_json::to_string(&Bytes::default()).unwrap(), "\"0x\"");
+        assert_eq!(serde_json::to_string(&Bytes(vec![0, 0xab])).unwrap(), "\"0x00ab\"");
+        assert_eq!(B256::from(U256::from(1)).to_string(), format!("0x{}01", "0".repeat(62)));
+    }
+
+    #[test]
+    fn parses_addresses_and_bytes() {
+        let address = "000000000000000000000000000000000000abcd".parse::<Address>().unwrap();
+        assert_eq!(address, Address::from_low_u64(0xabcd));
+        assert!("0xabcd".parse::<Address>().is_err());
+        assert!(format!("0x{}", "00".repeat(21)).parse::<Address>().is_err());
+        assert!(serde_json::from_str::<Address>("1").is_err());
+
+        assert_eq!("0x".parse::<Bytes>().unwrap(), Bytes::default());
+        assert_eq!("0x00AB".parse::<Bytes>().unwrap(), Bytes(vec![0, 0xab]));
+        assert_eq!("00ab".parse::<Bytes>().unwrap(), Bytes(vec![0, 0xab]));
+        assert!("0xabc".parse::<Bytes>().is_err());
+        assert!("0xzz".parse::<Bytes>().is_err());
+    }
+}